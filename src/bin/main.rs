@@ -1,55 +1,431 @@
-use rust_ray::util::{clamp, Ray, Vec3, simple_scene, Sample};
+use rust_ray::util::{load_scene_file, model_scene, scene_description, seed_rng, Checkpoint, Film, Filter, OutputFormat, ProgressBar, RenderSettings, RenderState, Renderer, TileOrder, ToneMap, ToneMapOperator, FILTER_NAMES, SCENE_NAMES, TILE_ORDER_NAMES, TONE_MAP_NAMES};
 
-use rand::{thread_rng, Rng};
-use rayon::prelude::*;
-use std::time::Instant;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+// Stands in for no limit on the samples, while being small enough to be kept in a checkpoint
+const UNLIMITED_SAMPLES: usize = u32::MAX as usize;
+
+const USAGE: &str = "Usage: main [options]
+
+Options:
+    -w, --width <pixels>      Image width (default 2400, or as set by the scene file)
+    -h, --height <pixels>     Image height (default 800, or as set by the scene file)
+    -s, --samples <count>     Samples per pixel (default 128, or as set by the scene file, and no limit with --time or --noise)
+        --pass <samples>      Samples per pixel added to the whole image in each progressive pass (default 16, or as set by the scene file)
+    -d, --depth <bounces>     Maximum ray depth (default 50, or as set by the scene file)
+        --roulette <bounces>  Depth after which paths may end early by Russian roulette (default 3, or as set by the scene file)
+        --scene <name|path>   Built-in scene name, or path to a TOML scene file or OBJ model (default simple)
+        --export <path>       Write the scene as a TOML scene file instead of rendering
+    -o, --output <path>       Output image, format taken from the extension (default output.png)
+        --format <format>     Output format overriding the extension (exr, pfm, hdr for linear floats, or png, jpeg, bmp, pnm, tiff, tga, ico)
+        --tonemap <operator>  Tone mapping for 8-bit formats: clamp, reinhard, aces or agx (default clamp, or as set by the scene file)
+        --exposure <stops>    Exposure adjustment before tone mapping (default 0, or as set by the scene file)
+        --filter <filter>     Reconstruction filter: box, tent, gaussian, mitchell or lanczos (default box, or as set by the scene file)
+        --tile-size <pixels>  Side of the tiles rendered by each thread (default 32, or as set by the scene file)
+        --tile-order <order>  Order tiles are rendered in: scanline, spiral or hilbert (default spiral, or as set by the scene file)
+        --time <duration>     Time limit, in seconds or with an s, m or h suffix, after which no more passes are started
+        --noise <level>       Relative noise at which pixels stop being sampled, such as 0.02, ending the render when all have
+        --checkpoint <path>   Save the render between passes so it can be resumed, along with a preview image
+        --interval <seconds>  Time between checkpoints (default 300)
+        --preview <path>      Preview image written with each checkpoint (default next to the checkpoint, as PNG)
+        --resume <path>       Continue the render in a checkpoint up to the samples set by -s, saving back to it
+        --quiet               Do not show the progress bar
+    -t, --threads <count>     Number of render threads (default all cores)
+        --seed <seed>         Seed for reproducible renders
+        --help                Print this message";
+
+struct Options {
+    width: Option<usize>,
+    height: Option<usize>,
+    samples: Option<usize>,
+    pass_samples: Option<usize>,
+    time_limit: Option<f64>,
+    noise_target: Option<f64>,
+    max_depth: Option<usize>,
+    roulette_depth: Option<usize>,
+    scene: Option<String>,
+    export: Option<String>,
+    output: String,
+    format: Option<OutputFormat>,
+    tone_map: Option<ToneMapOperator>,
+    exposure: Option<f64>,
+    filter: Option<Filter>,
+    tile_size: Option<usize>,
+    tile_order: Option<TileOrder>,
+    checkpoint: Option<PathBuf>,
+    interval: u64,
+    preview: Option<PathBuf>,
+    resume: Option<PathBuf>,
+    quiet: bool,
+    threads: Option<usize>,
+    seed: Option<u64>
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            width: None,
+            height: None,
+            samples: None,
+            pass_samples: None,
+            time_limit: None,
+            noise_target: None,
+            max_depth: None,
+            roulette_depth: None,
+            scene: None,
+            export: None,
+            output: String::from("output.png"),
+            format: None,
+            tone_map: None,
+            exposure: None,
+            filter: None,
+            tile_size: None,
+            tile_order: None,
+            checkpoint: None,
+            interval: 300,
+            preview: None,
+            resume: None,
+            quiet: false,
+            threads: None,
+            seed: None
+        }
+    }
+}
+
+// What the command line asks for, which is a render or export unless it only wants the usage
+enum Command {
+    Help,
+    Run(Box<Options>)
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(flag) = args.next() {
+        if flag == "--help" {
+            return Ok(Command::Help)
+        }
+        if flag == "--quiet" {
+            options.quiet = true;
+            continue
+        }
+
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+        let number = || value.parse::<usize>().map_err(|_| format!("invalid value '{}' for {}", value, flag));
+
+        match flag.as_str() {
+            "-w" | "--width" => options.width = Some(number()?),
+            "-h" | "--height" => options.height = Some(number()?),
+            "-s" | "--samples" => options.samples = Some(number()?),
+            "--pass" => options.pass_samples = Some(number()?),
+            "--time" => options.time_limit = Some(parse_duration(value).ok_or_else(|| format!("invalid duration '{}' for {}", value, flag))?),
+            "--noise" => options.noise_target = Some(value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))?),
+            "-d" | "--depth" => options.max_depth = Some(number()?),
+            "--roulette" => options.roulette_depth = Some(number()?),
+            "--scene" => options.scene = Some(value.clone()),
+            "--export" => options.export = Some(value.clone()),
+            "-o" | "--output" => options.output = value.clone(),
+            "--format" => options.format = Some(OutputFormat::from_name(value).ok_or_else(|| format!("unsupported output format '{}'", value))?),
+            "--tonemap" => {
+                let operator = ToneMapOperator::from_name(value);
+                options.tone_map = Some(operator.ok_or_else(|| format!("unknown tone mapping '{}', expected one of {}", value, TONE_MAP_NAMES.join(", ")))?)
+            }
+            "--exposure" => options.exposure = Some(value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))?),
+            "--filter" => {
+                let filter = Filter::from_name(value);
+                options.filter = Some(filter.ok_or_else(|| format!("unknown filter '{}', expected one of {}", value, FILTER_NAMES.join(", ")))?)
+            }
+            "--tile-size" => options.tile_size = Some(number()?),
+            "--tile-order" => {
+                let order = TileOrder::from_name(value);
+                options.tile_order = Some(order.ok_or_else(|| format!("unknown tile order '{}', expected one of {}", value, TILE_ORDER_NAMES.join(", ")))?)
+            }
+            "--checkpoint" => options.checkpoint = Some(PathBuf::from(value)),
+            "--interval" => options.interval = value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))?,
+            "--preview" => options.preview = Some(PathBuf::from(value)),
+            "--resume" => options.resume = Some(PathBuf::from(value)),
+            "-t" | "--threads" => options.threads = Some(number()?),
+            "--seed" => options.seed = Some(value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))?),
+            _ => return Err(format!("unknown option {}", flag))
+        }
+    }
+
+    if options.width == Some(0) || options.height == Some(0) || options.samples == Some(0) || options.pass_samples == Some(0) || options.tile_size == Some(0) {
+        return Err(String::from("width, height, samples, pass samples and tile size must be greater than zero"))
+    }
+    if options.noise_target.is_some_and(|target| target <= 0.0) {
+        return Err(String::from("the noise target must be positive"))
+    }
+
+    // Anything that changes the samples already taken is fixed by the checkpoint
+    let fixed = options.width.is_some() || options.height.is_some() || options.pass_samples.is_some() || options.max_depth.is_some() || options.roulette_depth.is_some()
+        || options.filter.is_some() || options.scene.is_some() || options.seed.is_some();
+    if options.resume.is_some() && fixed {
+        return Err(String::from("only the samples, tiles, output and threads can be set when resuming"))
+    }
+    Ok(Command::Run(Box::new(options)))
+}
+
+// Seconds, minutes or hours, taking plain numbers as seconds
+fn parse_duration(value: &str) -> Option<f64> {
+    let (number, unit) = match value.char_indices().last()? {
+        (index, 's') => (&value[..index], 1.0),
+        (index, 'm') => (&value[..index], 60.0),
+        (index, 'h') => (&value[..index], 3600.0),
+        _ => (value, 1.0)
+    };
+
+    let seconds = number.parse::<f64>().ok()? * unit;
+    if seconds > 0.0 && seconds.is_finite() {
+        Some(seconds)
+    } else {
+        None
+    }
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
 
 fn main() {
-    let width: usize = 2400;
-    let height: usize = 800;
-    let max_color: f64 = 255.999;
-    let samples = 128;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args).unwrap_or_else(|e| fail(format!("{}\n\n{}", e, USAGE))) {
+        Command::Help => {
+            println!("{}", USAGE);
+            return
+        }
+        Command::Run(options) => *options
+    };
+
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap_or_else(|e| fail(e.to_string()));
+    }
+
+    let resumed = options.resume.as_ref().map(|path| {
+        Checkpoint::load(path).unwrap_or_else(|e| fail(format!("could not resume from {}: {}", path.display(), e)))
+    });
+
+    // Scenes built with random numbers come out the same again when resumed from the seed
+    let seed = resumed.as_ref().map(|(checkpoint, _)| checkpoint.seed).or(options.seed).unwrap_or_else(rand::random);
+    seed_rng(seed);
+
+    let scene_name = match (&resumed, &options.scene) {
+        (Some((checkpoint, _)), _) => checkpoint.scene.clone(),
+        (None, Some(scene)) => scene.clone(),
+        (None, None) => String::from("simple")
+    };
+    let scene_path = Path::new(&scene_name);
+    let description = match scene_description(&scene_name) {
+        Some(description) => Some(description),
+        None if scene_path.is_file() && scene_path.extension().is_some_and(|e| e == "toml") => {
+            Some(load_scene_file(scene_path).unwrap_or_else(|e| fail(format!("{}: {}", scene_name, e))))
+        }
+        None if scene_path.is_file() => None,
+        None => fail(format!("'{}' is neither a scene ({}) nor a file", scene_name, SCENE_NAMES.join(", ")))
+    };
+
+    if let Some(export) = &options.export {
+        let description = description.unwrap_or_else(|| fail(String::from("only built-in scenes and scene files can be exported")));
+        description.save(export).unwrap_or_else(|e| fail(e.to_string()));
+        println!("Wrote {}", export);
+        return
+    }
+
+    // Command line options take precedence over the settings stored with the scene or checkpoint
+    let mut settings = match &resumed {
+        Some((checkpoint, _)) => checkpoint.settings.clone(),
+        None => description.as_ref().map(|d| d.render.clone()).unwrap_or_default()
+    };
+    settings.width = options.width.unwrap_or(settings.width);
+    settings.height = options.height.unwrap_or(settings.height);
+    settings.samples = options.samples.unwrap_or(settings.samples);
+    settings.pass_samples = options.pass_samples.unwrap_or(settings.pass_samples);
+    settings.time_limit = options.time_limit.or(settings.time_limit);
+    settings.noise_target = options.noise_target.or(settings.noise_target);
+    if options.samples.is_none() && (options.time_limit.is_some() || options.noise_target.is_some()) {
+        settings.samples = UNLIMITED_SAMPLES;
+    }
+    settings.max_depth = options.max_depth.unwrap_or(settings.max_depth);
+    settings.roulette_depth = options.roulette_depth.unwrap_or(settings.roulette_depth);
+    settings.filter = options.filter.unwrap_or(settings.filter);
+    settings.tile_size = options.tile_size.unwrap_or(settings.tile_size);
+    settings.tile_order = options.tile_order.unwrap_or(settings.tile_order);
+    let tone_map = ToneMap::new(options.tone_map.unwrap_or(settings.tone_map), options.exposure.unwrap_or(settings.exposure));
+    let RenderSettings { width, height, .. } = settings;
+
+    let format = options.format
+        .or_else(|| OutputFormat::from_path(&options.output))
+        .unwrap_or_else(|| fail(format!("cannot tell the format of {}, use --format to choose one", options.output)));
+
+    // A resumed render goes on saving to the checkpoint it came from
+    let checkpoint_path = options.checkpoint.clone().or_else(|| options.resume.clone());
+    let preview_path = options.preview.clone().or_else(|| checkpoint_path.as_ref().map(|path| path.with_extension("preview.png")));
+    let preview_format = preview_path.as_ref().map(|path| OutputFormat::from_path(path).unwrap_or(OutputFormat::Ldr(image::ImageFormat::PNG)));
+
     let time = Instant::now();
 
     print!("Building environment...");
-    let (camera, world) = simple_scene(width, height);
+    let scene = match &description {
+        Some(description) => description.build(width, height),
+        None => model_scene(&scene_name, width, height)
+    };
+    let (camera, scene) = scene.unwrap_or_else(|e| fail(e.to_string()));
 
-    println!(" {} objects, {}ms", world.list.len(), time.elapsed().as_millis());
+    println!(" {} objects, {} lights, {}ms", scene.world().len(), scene.light_count(), time.elapsed().as_millis());
     let time = Instant::now();
 
-    print!("Sampling rays...");
-    let pixels = (0..height)
-        .into_par_iter()
-        .rev()
-        .map(|j| {
-            let mut part = Vec::with_capacity(width * 3);
-            for i in 0..width {
-                let mut rng = thread_rng();
-                let mut col: Vec3 = Vec3::new(0.0, 0.0, 0.0);
-
-                for _s in 0..samples {
-                    let u: f64 = (i as f64 + rng.gen::<f64>()) / width as f64;
-                    let v: f64 = (j as f64 + rng.gen::<f64>()) / height as f64;
-                    let r: Ray = camera.get_ray(u, v);
-                    col += Sample::new(r, 50, &world).last().unwrap();
-                }
-
-                col = col / samples as f64;
-                col = Vec3::new(col.x.sqrt(), col.y.sqrt(), col.z.sqrt());
-
-                let ir: u8 = (max_color * clamp(col.x, 0.0, 1.0)) as u8;
-                let ig: u8 = (max_color * clamp(col.y, 0.0, 1.0)) as u8;
-                let ib: u8 = (max_color * clamp(col.z, 0.0, 1.0)) as u8;
-
-                part.push(ir);
-                part.push(ig);
-                part.push(ib);
+    let (film, start) = match resumed {
+        Some((checkpoint, film)) => {
+            println!("Resuming from {} samples per pixel", checkpoint.state.samples);
+            (film, checkpoint.state)
+        }
+        None => (Film::new(width, height, settings.filter), RenderState::default())
+    };
+
+    // Files are found again by their full path when resumed from another directory
+    let checkpoint_scene = match fs::canonicalize(&scene_name) {
+        Ok(path) if scene_description(&scene_name).is_none() => path.to_string_lossy().into_owned(),
+        _ => scene_name.clone()
+    };
+    let mut checkpoint = Checkpoint {
+        scene: checkpoint_scene,
+        settings: settings.clone(),
+        seed,
+        state: start
+    };
+
+    let renderer = Renderer::new(&camera, &scene, settings, Some(seed));
+    println!("Sampling rays in {} tiles...", renderer.tiles().len());
+
+    // Failing to save is reported, but the render carries on
+    let save = |film: &Film, checkpoint: &Checkpoint| {
+        if let Some(path) = &checkpoint_path {
+            if let Err(e) = checkpoint.save(path, film) {
+                eprintln!("warning: could not write checkpoint {}: {}", path.display(), e);
+            }
+        }
+        if let (Some(path), Some(format)) = (&preview_path, preview_format) {
+            if let Err(e) = film.save(path, format, &tone_map) {
+                eprintln!("warning: could not write preview {}: {}", path.display(), e);
+            }
+        }
+    };
+
+    let progress = ProgressBar::new();
+    let mut last_saved = Instant::now();
+    let interval = Duration::from_secs(options.interval);
+    let samples_before = film.sample_count();
+
+    let end = renderer.render_passes(
+        &film,
+        start,
+        |tile| {
+            if !options.quiet {
+                progress.update(tile);
             }
-            part
-        })
-        .flatten()
-        .collect::<Vec<u8>>();
-    println!(" {} rays, {}ms", width * height * samples, time.elapsed().as_millis());
+        },
+        |film, state| {
+            if last_saved.elapsed() >= interval {
+                last_saved = Instant::now();
+                checkpoint.state = state;
+                save(film, &checkpoint);
+            }
+        }
+    );
+    progress.finish();
+
+    // The last pass is only known once the render has stopped
+    if checkpoint.state != end {
+        checkpoint.state = end;
+        save(&film, &checkpoint);
+    }
+    println!("Sampled {} rays in {}ms, {} passes and up to {} samples per pixel", film.sample_count() - samples_before, time.elapsed().as_millis(), end.passes, end.samples);
+
+    film.save(&options.output, format, &tone_map)
+        .unwrap_or_else(|e| fail(format!("could not write {}: {}", options.output, e)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        match parse_args(&args)? {
+            Command::Help => Err(String::from("help")),
+            Command::Run(options) => Ok(*options)
+        }
+    }
+
+    #[test]
+    fn help() {
+        assert!(matches!(parse_args(&[String::from("--help")]), Ok(Command::Help)));
+        assert!(matches!(parse_args(&[String::from("-w"), String::from("10"), String::from("--help")]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn flags() {
+        let options = parse("-w 320 --height 240 -s 64 --pass 8 -d 12 --roulette 4 --scene random -o out.exr --format pfm --tonemap AgX \
+            --exposure -1.5 --filter mitchell --tile-size 16 --tile-order hilbert --time 2m --noise 0.05 --checkpoint a.ckpt --interval 60 \
+            --preview p.png -t 3 --seed 42 --quiet").unwrap();
+        assert_eq!((options.width, options.height, options.samples, options.pass_samples), (Some(320), Some(240), Some(64), Some(8)));
+        assert_eq!((options.max_depth, options.roulette_depth), (Some(12), Some(4)));
+        assert_eq!((options.scene.as_deref(), options.output.as_str()), (Some("random"), "out.exr"));
+        assert_eq!(options.format, OutputFormat::from_name("pfm"));
+        assert_eq!((options.tone_map, options.exposure), (Some(ToneMapOperator::Agx), Some(-1.5)));
+        assert_eq!(options.filter, Filter::from_name("mitchell"));
+        assert_eq!((options.tile_size, options.tile_order), (Some(16), Some(TileOrder::Hilbert)));
+        assert_eq!((options.time_limit, options.noise_target), (Some(120.0), Some(0.05)));
+        assert_eq!((options.checkpoint, options.interval, options.preview), (Some(PathBuf::from("a.ckpt")), 60, Some(PathBuf::from("p.png"))));
+        assert_eq!((options.threads, options.seed, options.quiet), (Some(3), Some(42), true));
+
+        let defaults = parse("").unwrap();
+        assert_eq!((defaults.output.as_str(), defaults.interval, defaults.quiet), ("output.png", 300, false));
+        assert!(defaults.width.is_none() && defaults.samples.is_none() && defaults.scene.is_none() && defaults.resume.is_none());
+    }
+
+    #[test]
+    fn invalid_values() {
+        for args in ["--width", "-w ten", "-s -1", "--tonemap filmic", "--filter sinc", "--tile-order random", "--format gif", "--time soon", "--noise x", "--bogus 1"].iter() {
+            assert!(parse(args).is_err(), "{}", args);
+        }
+        for args in ["-w 0", "-h 0", "-s 0", "--pass 0", "--tile-size 0", "--noise 0", "--noise -0.1", "--time 0s"].iter() {
+            assert!(parse(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn resume_conflicts() {
+        // What is rendered comes from the checkpoint, and only how much more of it and where it goes can change
+        let options = parse("--resume a.ckpt -s 256 --tile-size 8 --tile-order scanline -o b.png --tonemap aces -t 2 --time 1h --noise 0.01 --quiet").unwrap();
+        assert_eq!((options.resume, options.samples), (Some(PathBuf::from("a.ckpt")), Some(256)));
+
+        for flag in ["-w 10", "-h 10", "--pass 4", "-d 4", "--roulette 2", "--filter tent", "--scene simple", "--seed 1"].iter() {
+            assert!(parse(&format!("--resume a.ckpt {}", flag)).is_err(), "{}", flag);
+            assert!(parse(&format!("{} --resume a.ckpt", flag)).is_err(), "{}", flag);
+            assert!(parse(flag).is_ok(), "{}", flag);
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("10m"), Some(600.0));
+        assert_eq!(parse_duration("1.5h"), Some(5400.0));
+        assert_eq!(parse_duration("90s"), Some(90.0));
+        assert_eq!(parse_duration("45"), Some(45.0));
+        assert_eq!(parse_duration("0.5"), Some(0.5));
 
-    image::save_buffer("output.png", &pixels, width as u32, height as u32, image::RGB(8)).unwrap();
+        // A render needs some time, and a unit needs a number
+        for value in ["0s", "0", "-5m", "inf", "NaN", "m", "", "10d", "1 h"].iter() {
+            assert_eq!(parse_duration(value), None, "{}", value);
+        }
+    }
 }
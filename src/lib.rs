@@ -1,3 +1,5 @@
 pub mod material;
 pub mod util;
-pub mod objects;
\ No newline at end of file
+pub mod objects;
+pub mod texture;
+pub mod light;
\ No newline at end of file
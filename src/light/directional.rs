@@ -0,0 +1,35 @@
+use crate::light::Light;
+use crate::objects::LightSample;
+use crate::util::Vec3;
+
+// Parallel light arriving from infinitely far away, such as the sun
+pub struct DirectionalLight {
+    // Unit direction the light travels in
+    direction: Vec3,
+    // Irradiance on a surface facing the light
+    irradiance: Vec3
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Vec3) -> DirectionalLight {
+        DirectionalLight {
+            direction: direction.unit(),
+            irradiance
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_light(&self, _origin: Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: f64::INFINITY,
+            emission: self.irradiance,
+            pdf: 1.0
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,72 @@
+use rand::Rng;
+use crate::light::Light;
+use crate::objects::{area_pdf, LightSample};
+use crate::util::{coordinate_system, rng, Ray, Vec3};
+
+// Round panel giving off light from the side its normal points to
+pub struct DiskLight {
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+    radiance: Vec3
+}
+
+impl DiskLight {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, radiance: Vec3) -> DiskLight {
+        DiskLight {
+            center,
+            normal: normal.unit(),
+            radius,
+            radiance
+        }
+    }
+
+    fn area(&self) -> f64 {
+        std::f64::consts::PI * self.radius * self.radius
+    }
+
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let denominator = ray.direction.dot(self.normal);
+        if denominator == 0.0 {
+            return None
+        }
+
+        let t = (self.center - ray.origin).dot(self.normal) / denominator;
+        if t < t_min || t > t_max || (ray.point_at_parameter(t) - self.center).squared_length() > self.radius * self.radius {
+            return None
+        }
+        Some(t)
+    }
+}
+
+impl Light for DiskLight {
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let mut rng = rng();
+        let r = self.radius * rng.gen::<f64>().sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let (u, v) = coordinate_system(self.normal);
+        let point = self.center + u * (r * phi.cos()) + v * (r * phi.sin());
+
+        if (origin - point).dot(self.normal) <= 0.0 {
+            return None
+        }
+        LightSample::from_area(origin, point, self.normal, self.radiance, self.area())
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = Ray::new(origin, direction);
+
+        match self.intersect(ray, 0.001, f64::INFINITY) {
+            Some(t) if direction.dot(self.normal) < 0.0 => area_pdf(origin, ray.point_at_parameter(t), self.normal, self.area()),
+            _ => 0.0
+        }
+    }
+
+    // As with rectangles, the back of the disk is neither seen nor casts shadows
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        if ray.direction.dot(self.normal) >= 0.0 {
+            return None
+        }
+        self.intersect(ray, t_min, t_max).map(|t| (t, self.radiance))
+    }
+}
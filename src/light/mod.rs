@@ -0,0 +1,125 @@
+use crate::objects::LightSample;
+use crate::util::{Ray, Vec3};
+
+pub mod point;
+pub mod spot;
+pub mod directional;
+pub mod rect;
+pub mod disk;
+
+pub use point::PointLight;
+pub use spot::SpotLight;
+pub use directional::DirectionalLight;
+pub use rect::RectLight;
+pub use disk::DiskLight;
+
+// Light source placed in the scene on its own rather than as an emissive object. Every light is
+// sampled by the integrator at each diffuse or glossy bounce.
+pub trait Light: Send + Sync {
+    // Picks a direction from the origin towards the light
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample>;
+
+    // Density with respect to solid angle of `sample_light` picking the direction from the origin
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // Lights which are a single point or direction can only be reached by sampling them, so their
+    // samples carry a density of one and are not weighted against the material
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    // Distance along the ray to the light and the radiance seen there, for lights with an area
+    fn hit(&self, _ray: Ray, _t_min: f64, _t_max: f64) -> Option<(f64, Vec3)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::checks::{relative_difference, sphere_directions};
+    use crate::util::seed_rng;
+    use std::f64::consts::PI;
+
+    // Panels of about a unit across at the origin, facing down the y axis
+    fn area_lights() -> Vec<(&'static str, Box<dyn Light>)> {
+        let radiance = Vec3::new(1.0, 1.0, 1.0);
+        vec![
+            ("rect", Box::new(RectLight::new(Vec3::new(-0.5, 0.0, -0.5), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), radiance))),
+            ("tilted rect", Box::new(RectLight::centered(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.3, -1.0, 0.2), 1.5, 0.5, radiance))),
+            ("disk", Box::new(DiskLight::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.5, radiance)))
+        ]
+    }
+
+    fn in_front() -> Vec<Vec3> {
+        vec![Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.3, -0.4, 0.2), Vec3::new(2.0, -0.5, -1.0), Vec3::new(0.1, -5.0, 0.0)]
+    }
+
+    #[test]
+    fn area_samples_match_pdf() {
+        seed_rng(1);
+        for (name, light) in area_lights() {
+            for origin in in_front() {
+                for _ in 0..1000 {
+                    let sample = light.sample_light(origin).unwrap();
+                    let pdf = light.pdf_value(origin, sample.direction);
+                    assert!(relative_difference(sample.pdf, pdf) < 1e-6, "{} from {:?}: {} against {}", name, [origin.x, origin.y, origin.z], sample.pdf, pdf);
+
+                    // The sample lies on the light, where its radiance is seen
+                    let (t, radiance) = light.hit(Ray::new(origin, sample.direction), 0.001, f64::INFINITY).unwrap();
+                    assert!((t - sample.distance).abs() < 1e-9);
+                    assert_eq!([radiance.x, radiance.y, radiance.z], [sample.emission.x, sample.emission.y, sample.emission.z]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn area_pdf_integrates_to_one() {
+        let directions = sphere_directions(400_000);
+        for (name, light) in area_lights() {
+            for origin in in_front() {
+                let integral = directions.iter().map(|&direction| light.pdf_value(origin, direction)).sum::<f64>() * (4.0 * PI / directions.len() as f64);
+                assert!((integral - 1.0).abs() < 0.02, "{} from {:?}: {}", name, [origin.x, origin.y, origin.z], integral);
+            }
+        }
+    }
+
+    #[test]
+    fn area_lights_are_dark_from_behind() {
+        seed_rng(2);
+        for (name, light) in area_lights() {
+            let origin = Vec3::new(0.1, 1.0, 0.1);
+            assert!(light.sample_light(origin).is_none(), "{}", name);
+            assert_eq!(light.pdf_value(origin, Vec3::new(0.0, -1.0, 0.0)), 0.0, "{}", name);
+            assert!(light.hit(Ray::new(origin, Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).is_none(), "{}", name);
+        }
+    }
+
+    // Lights at a point or in a direction have no density to evaluate: their samples carry a pdf
+    // of one that the integrator does not weight, and no other strategy can find them
+    #[test]
+    fn delta_lights() {
+        let lights: Vec<Box<dyn Light>> = vec![
+            Box::new(PointLight::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(4.0, 4.0, 4.0))),
+            Box::new(SpotLight::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), Vec3::new(4.0, 4.0, 4.0), 30.0, 5.0)),
+            Box::new(DirectionalLight::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(4.0, 4.0, 4.0)))
+        ];
+        for light in lights {
+            assert!(light.is_delta());
+            let sample = light.sample_light(Vec3::new(0.0, 0.0, 0.0)).unwrap();
+            assert_eq!(sample.pdf, 1.0);
+            assert_eq!([sample.direction.x, sample.direction.y, sample.direction.z], [0.0, 1.0, 0.0]);
+            assert_eq!(light.pdf_value(Vec3::new(0.0, 0.0, 0.0), sample.direction), 0.0);
+            assert!(light.hit(Ray::new(Vec3::new(0.0, 0.0, 0.0), sample.direction), 0.001, f64::INFINITY).is_none());
+        }
+
+        // Point and spot lights fall off with the square of the distance, unlike the directional one
+        let point = PointLight::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(4.0, 4.0, 4.0)).sample_light(Vec3::new(0.0, 0.0, 0.0)).unwrap();
+        assert_eq!((point.distance, point.emission.x), (2.0, 1.0));
+        let sun = DirectionalLight::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(4.0, 4.0, 4.0)).sample_light(Vec3::new(0.0, 0.0, 0.0)).unwrap();
+        assert_eq!((sun.distance, sun.emission.x), (f64::INFINITY, 4.0));
+    }
+}
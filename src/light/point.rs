@@ -0,0 +1,41 @@
+use crate::light::Light;
+use crate::objects::LightSample;
+use crate::util::Vec3;
+
+// Shines equally in every direction from a single point
+pub struct PointLight {
+    position: Vec3,
+    // Radiant intensity, the power sent into each unit of solid angle
+    intensity: Vec3
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Vec3) -> PointLight {
+        PointLight {
+            position,
+            intensity
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let to_light = self.position - origin;
+        let distance_squared = to_light.squared_length();
+        if distance_squared == 0.0 {
+            return None
+        }
+
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            emission: self.intensity / distance_squared,
+            pdf: 1.0
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
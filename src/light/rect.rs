@@ -0,0 +1,96 @@
+use rand::Rng;
+use crate::light::Light;
+use crate::objects::{area_pdf, LightSample};
+use crate::util::{rng, Ray, Vec3};
+
+// Parallelogram spanned by two edges from a corner, giving off light from the side the cross
+// product of the edges points to
+pub struct RectLight {
+    corner: Vec3,
+    edge_u: Vec3,
+    edge_v: Vec3,
+    normal: Vec3,
+    area: f64,
+    radiance: Vec3
+}
+
+impl RectLight {
+    pub fn new(corner: Vec3, edge_u: Vec3, edge_v: Vec3, radiance: Vec3) -> RectLight {
+        let cross = edge_u.cross(edge_v);
+
+        RectLight {
+            corner,
+            edge_u,
+            edge_v,
+            normal: cross.unit(),
+            area: cross.length(),
+            radiance
+        }
+    }
+
+    // Rectangle of the given size centered on a point and facing along the normal, with its
+    // height running along whichever of the y and z axes is further from the normal
+    pub fn centered(center: Vec3, normal: Vec3, width: f64, height: f64, radiance: Vec3) -> RectLight {
+        let normal = normal.unit();
+        let reference = if normal.y.abs() < 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(0.0, 0.0, 1.0) };
+        let u = reference.cross(normal).unit();
+        let v = normal.cross(u);
+
+        let (edge_u, edge_v) = (u * width, v * height);
+        RectLight::new(center - edge_u * 0.5 - edge_v * 0.5, edge_u, edge_v, radiance)
+    }
+
+    // Distance along the ray to the rectangle, whichever side it is approached from
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let denominator = ray.direction.dot(self.normal);
+        if denominator == 0.0 || self.area <= 0.0 {
+            return None
+        }
+
+        let t = (self.corner - ray.origin).dot(self.normal) / denominator;
+        if t < t_min || t > t_max {
+            return None
+        }
+
+        // Coordinates of the point along the edges, from 0 to 1 inside
+        let offset = ray.point_at_parameter(t) - self.corner;
+        let w = self.normal / self.area;
+        let alpha = w.dot(offset.cross(self.edge_v));
+        let beta = w.dot(self.edge_u.cross(offset));
+
+        if (0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+impl Light for RectLight {
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let mut rng = rng();
+        let point = self.corner + self.edge_u * rng.gen::<f64>() + self.edge_v * rng.gen::<f64>();
+
+        if (origin - point).dot(self.normal) <= 0.0 {
+            return None
+        }
+        LightSample::from_area(origin, point, self.normal, self.radiance, self.area)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = Ray::new(origin, direction);
+
+        match self.intersect(ray, 0.001, f64::INFINITY) {
+            Some(t) if direction.dot(self.normal) < 0.0 => area_pdf(origin, ray.point_at_parameter(t), self.normal, self.area),
+            _ => 0.0
+        }
+    }
+
+    // The back of the light is not seen at all, just as it casts no shadows
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        if ray.direction.dot(self.normal) >= 0.0 {
+            return None
+        }
+        self.intersect(ray, t_min, t_max).map(|t| (t, self.radiance))
+    }
+}
@@ -0,0 +1,105 @@
+use crate::light::Light;
+use crate::objects::LightSample;
+use crate::util::Vec3;
+
+// Point light limited to a cone, fading out smoothly towards its edge
+pub struct SpotLight {
+    position: Vec3,
+    // Unit axis of the cone
+    direction: Vec3,
+    intensity: Vec3,
+    // Cosines of the angles from the axis where the light starts to fade and where it is gone
+    cos_falloff_start: f64,
+    cos_total: f64
+}
+
+impl SpotLight {
+    // The angle is the half-width of the cone in degrees, the last `falloff` degrees of which fade out
+    pub fn new(position: Vec3, direction: Vec3, intensity: Vec3, angle: f64, falloff: f64) -> SpotLight {
+        SpotLight {
+            position,
+            direction: direction.unit(),
+            intensity,
+            cos_falloff_start: (angle - falloff).max(0.0).to_radians().cos(),
+            cos_total: angle.to_radians().cos()
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_falloff_start {
+            1.0
+        } else if cos_theta <= self.cos_total {
+            0.0
+        } else {
+            let t = (cos_theta - self.cos_total) / (self.cos_falloff_start - self.cos_total);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let to_light = self.position - origin;
+        let distance_squared = to_light.squared_length();
+        if distance_squared == 0.0 {
+            return None
+        }
+
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff <= 0.0 {
+            return None
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            emission: self.intensity * (falloff / distance_squared),
+            pdf: 1.0
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Emission seen from a point a unit away from the light, at an angle in degrees from its axis
+    fn emission_at(light: &SpotLight, angle: f64) -> f64 {
+        let angle = angle.to_radians();
+        let origin = Vec3::new(angle.sin(), 1.0 - angle.cos(), 0.0);
+        light.sample_light(origin).map_or(0.0, |sample| sample.emission.x)
+    }
+
+    #[test]
+    fn cone_falloff() {
+        let light = SpotLight::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), Vec3::new(2.0, 2.0, 2.0), 30.0, 10.0);
+
+        // Full strength out to the start of the falloff
+        assert!((emission_at(&light, 0.0) - 2.0).abs() < 1e-9);
+        assert!((emission_at(&light, 19.99) - 2.0).abs() < 1e-9);
+
+        // Fading smoothly down to nothing at the edge of the cone, and beyond it
+        let mut previous = 2.0;
+        for step in 1..100 {
+            let emission = emission_at(&light, 20.0 + 0.1 * step as f64);
+            assert!(emission > 0.0 && emission < previous, "{} at {} degrees", emission, 20.0 + 0.1 * step as f64);
+            previous = emission;
+        }
+        assert!(emission_at(&light, 20.01) > 1.999);
+        assert!(emission_at(&light, 29.99) < 1e-4);
+        assert_eq!(emission_at(&light, 30.01), 0.0);
+        assert_eq!(emission_at(&light, 90.0), 0.0);
+        assert_eq!(emission_at(&light, 180.0), 0.0);
+
+        // Without a falloff the edge is hard
+        let hard = SpotLight::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), Vec3::new(2.0, 2.0, 2.0), 30.0, 0.0);
+        assert!((emission_at(&hard, 29.99) - 2.0).abs() < 1e-9);
+        assert_eq!(emission_at(&hard, 30.01), 0.0);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::objects::SurfaceInteraction;
+use crate::util::Vec3;
+
+// Direction picked by a BSDF together with what is needed to weight it
+pub struct BsdfSample {
+    pub direction: Vec3,
+    // Value of the BSDF times the cosine of the direction, divided by the pdf
+    pub weight: Vec3,
+    // Density with respect to solid angle, meaningless when the sample is specular
+    pub pdf: f64,
+    pub specular: bool
+}
+
+// Describes how a surface scatters light. All directions are unit vectors in the local shading
+// frame, where the outward normal is the z axis, and both point away from the surface: `wo`
+// towards where the light leaves to and `wi` towards where it arrives from.
+pub trait Bsdf: Send + Sync {
+    // Fraction of the light arriving along wi that leaves along wo, times the cosine of wi
+    fn eval(&self, surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> Vec3;
+
+    // Picks an incoming direction for the outgoing one, or None when the light is absorbed
+    fn sample(&self, surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample>;
+
+    // Density with respect to solid angle of `sample` returning wi
+    fn pdf(&self, surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f64;
+
+    // Light given off by the surface itself
+    fn emitted(&self) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    // Materials that only scatter into a handful of directions have nothing to evaluate, so
+    // lights are not sampled from them
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+// Whether two local directions lie on the same side of the surface
+pub fn same_hemisphere(a: Vec3, b: Vec3) -> bool {
+    a.z * b.z > 0.0
+}
+
+// Checks that hold for every BSDF, written against the trait alone so that a BSDF implemented
+// outside the crate can copy them into its own tests
+#[cfg(test)]
+pub(crate) mod checks {
+    use super::*;
+    use crate::material::{Lambertian, Material};
+    use crate::objects::{Hitable, Sphere};
+    use crate::texture::ConstantTexture;
+    use crate::util::{seed_rng, Ray};
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    // Runs the checks on the top of a unit sphere, where the shading frame is the world frame
+    pub fn with_surface<F: FnOnce(&SurfaceInteraction)>(check: F) {
+        let grey = Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))));
+        let sphere = Sphere::new(Vec3::new(0.0, 0.0, -1.0), 1.0, grey);
+        let r = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let (surface_interaction, _) = sphere.hit(r, 0.001, f64::MAX).unwrap();
+        check(&surface_interaction);
+    }
+
+    // Outgoing directions from grazing to straight up, on both sides of the surface
+    pub fn outgoing() -> Vec<Vec3> {
+        [0.05, 0.3, 0.7, 1.0]
+            .iter()
+            .flat_map(|&cos_theta: &f64| {
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                vec![Vec3::new(0.6 * sin_theta, 0.8 * sin_theta, cos_theta), Vec3::new(-sin_theta, 0.0, -cos_theta)]
+            })
+            .collect()
+    }
+
+    // Evenly spread directions over the sphere, each standing for the same solid angle
+    pub fn sphere_directions(count: usize) -> Vec<Vec3> {
+        let golden_angle = PI * (3.0 - 5f64.sqrt());
+        (0..count)
+            .map(|i| {
+                let z = 1.0 - (2.0 * i as f64 + 1.0) / count as f64;
+                let r = (1.0 - z * z).sqrt();
+                let phi = golden_angle * i as f64;
+                Vec3::new(r * phi.cos(), r * phi.sin(), z)
+            })
+            .collect()
+    }
+
+    pub fn relative_difference(a: f64, b: f64) -> f64 {
+        (a - b).abs() / a.abs().max(b.abs()).max(1e-12)
+    }
+
+    // Every sample reports the density `pdf` gives its direction and the weight `eval` over that density
+    pub fn assert_sample_matches_pdf(bsdf: &dyn Bsdf, surface_interaction: &SurfaceInteraction, samples: usize) {
+        seed_rng(1);
+        for wo in outgoing() {
+            for _ in 0..samples {
+                let sample = match bsdf.sample(surface_interaction, wo) {
+                    Some(sample) if !sample.specular => sample,
+                    _ => continue
+                };
+                let wi = sample.direction;
+                assert!((wi.length() - 1.0).abs() < 1e-9);
+
+                let pdf = bsdf.pdf(surface_interaction, wo, wi);
+                assert!(relative_difference(sample.pdf, pdf) < 1e-6, "wo {:?}: sample pdf {} but pdf {}", [wo.x, wo.y, wo.z], sample.pdf, pdf);
+
+                let weight = bsdf.eval(surface_interaction, wo, wi) / pdf;
+                for c in 0..3 {
+                    assert!(relative_difference(sample.weight[c], weight[c]) < 1e-6, "wo {:?}: weight {} but eval / pdf {}", [wo.x, wo.y, wo.z], sample.weight[c], weight[c]);
+                }
+            }
+        }
+    }
+
+    // `pdf` integrates over the sphere to the share of samples that are not absorbed, so it is the
+    // density of the directions `sample` returns rather than just proportional to it
+    pub fn assert_pdf_integrates(bsdf: &dyn Bsdf, surface_interaction: &SurfaceInteraction, tolerance: f64) {
+        let directions = sphere_directions(400_000);
+        seed_rng(2);
+        for wo in outgoing() {
+            let integral = directions.iter().map(|&wi| bsdf.pdf(surface_interaction, wo, wi)).sum::<f64>() * 4.0 * PI / directions.len() as f64;
+
+            let samples = 20_000;
+            let sampled = (0..samples).filter(|_| bsdf.sample(surface_interaction, wo).is_some_and(|sample| sample.pdf > 0.0)).count();
+            let expected = sampled as f64 / samples as f64;
+            assert!((integral - expected).abs() < tolerance, "wo {:?}: pdf integrates to {} for {} sampled", [wo.x, wo.y, wo.z], integral, expected);
+        }
+    }
+
+    // Swapping the directions of a reflection gives the same BSDF, which is `eval` without the cosine
+    pub fn assert_reciprocal(bsdf: &dyn Bsdf, surface_interaction: &SurfaceInteraction) {
+        let directions = sphere_directions(500);
+        for &wo in directions.iter() {
+            for &wi in directions.iter().filter(|wi| same_hemisphere(wo, **wi)) {
+                let forward = bsdf.eval(surface_interaction, wo, wi) / wi.z.abs();
+                let backward = bsdf.eval(surface_interaction, wi, wo) / wo.z.abs();
+                for c in 0..3 {
+                    assert!(relative_difference(forward[c], backward[c]) < 1e-9, "{:?} and {:?}: {} against {}", [wo.x, wo.y, wo.z], [wi.x, wi.y, wi.z], forward[c], backward[c]);
+                }
+            }
+        }
+    }
+
+    // Share of the light arriving along wo that the surface sends back out, by averaging the sample
+    // weights. Under uniform lighting of one, which is the white furnace, it is what the surface shows.
+    pub fn albedo(bsdf: &dyn Bsdf, surface_interaction: &SurfaceInteraction, wo: Vec3, samples: usize) -> Vec3 {
+        seed_rng(3);
+        let total = (0..samples)
+            .filter_map(|_| bsdf.sample(surface_interaction, wo))
+            .fold(Vec3::new(0.0, 0.0, 0.0), |total, sample| total + sample.weight);
+        total / samples as f64
+    }
+
+    // Averaging the sample weights and integrating `eval` over the sphere give the same albedo
+    pub fn assert_albedo_matches_eval(bsdf: &dyn Bsdf, surface_interaction: &SurfaceInteraction, tolerance: f64) {
+        let directions = sphere_directions(400_000);
+        for wo in outgoing() {
+            let integral = directions
+                .iter()
+                .fold(Vec3::new(0.0, 0.0, 0.0), |total, &wi| total + bsdf.eval(surface_interaction, wo, wi))
+                * (4.0 * PI / directions.len() as f64);
+            let albedo = albedo(bsdf, surface_interaction, wo, 20_000);
+            for c in 0..3 {
+                assert!((integral[c] - albedo[c]).abs() < tolerance, "wo {:?}: eval integrates to {} but samples average {}", [wo.x, wo.y, wo.z], integral[c], albedo[c]);
+            }
+        }
+    }
+
+    // The white furnace shows no more light than it is lit with from any direction
+    pub fn assert_energy_conserved(bsdf: &dyn Bsdf, surface_interaction: &SurfaceInteraction) {
+        for wo in outgoing() {
+            let albedo = albedo(bsdf, surface_interaction, wo, 20_000);
+            for c in 0..3 {
+                assert!(albedo[c] <= 1.01, "wo {:?}: albedo {}", [wo.x, wo.y, wo.z], albedo[c]);
+            }
+        }
+    }
+
+    // The checks above applied to the simplest BSDF, as an example of their use
+    #[test]
+    fn lambertian() {
+        let bsdf = Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.8, 0.5, 0.2))));
+        with_surface(|surface_interaction| {
+            assert_sample_matches_pdf(&bsdf, surface_interaction, 1000);
+            assert_pdf_integrates(&bsdf, surface_interaction, 0.01);
+            assert_reciprocal(&bsdf, surface_interaction);
+            assert_energy_conserved(&bsdf, surface_interaction);
+            assert_albedo_matches_eval(&bsdf, surface_interaction, 0.01);
+
+            let albedo = albedo(&bsdf, surface_interaction, Vec3::new(0.0, 0.6, 0.8), 1000);
+            assert!((albedo - Vec3::new(0.8, 0.5, 0.2)).length() < 1e-9);
+        });
+    }
+}
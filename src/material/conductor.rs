@@ -0,0 +1,183 @@
+use rand::Rng;
+use crate::material::{fresnel_conductor, reflect_about, same_hemisphere, Bsdf, BsdfSample, TrowbridgeReitz};
+use crate::objects::SurfaceInteraction;
+use crate::util::{rng, Vec3};
+
+// Measured complex refractive indices (eta, k) of common metals at the red, green and blue primaries
+pub fn complex_ior(name: &str) -> Option<(Vec3, Vec3)> {
+    let ior = match name {
+        "gold" => (Vec3::new(0.143, 0.374, 1.442), Vec3::new(3.983, 2.385, 1.603)),
+        "copper" => (Vec3::new(0.200, 0.924, 1.102), Vec3::new(3.912, 2.452, 2.142)),
+        "aluminium" | "aluminum" => (Vec3::new(1.657, 0.880, 0.521), Vec3::new(9.224, 6.270, 4.837)),
+        "silver" => (Vec3::new(0.155, 0.117, 0.138), Vec3::new(4.828, 3.122, 2.147)),
+        _ => return None
+    };
+    Some(ior)
+}
+
+// Metal with microfacet roughness and the Fresnel reflectance of its complex refractive index
+#[derive(Clone)]
+pub struct Conductor {
+    eta: Vec3,
+    k: Vec3,
+    distribution: TrowbridgeReitz
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f64, anisotropy: f64) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(roughness, anisotropy)
+        }
+    }
+}
+
+impl Bsdf for Conductor {
+    fn eval(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> Vec3 {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+
+        if self.is_specular() || !same_hemisphere(wo, wi) {
+            return black
+        }
+
+        let wm = wo + wi;
+        if wm.squared_length() == 0.0 {
+            return black
+        }
+        let wm = wm.unit();
+
+        let fresnel = fresnel_conductor(wo.dot(wm).abs(), self.eta, self.k);
+        fresnel * (self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z.abs()))
+    }
+
+    fn sample(&self, surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None
+        }
+
+        if self.is_specular() {
+            return Some(BsdfSample {
+                direction: Vec3::new(-wo.x, -wo.y, wo.z),
+                weight: fresnel_conductor(wo.z.abs(), self.eta, self.k),
+                pdf: 0.0,
+                specular: true
+            })
+        }
+
+        let mut rng = rng();
+        let wm = self.distribution.sample_visible(wo, [rng.gen::<f64>(), rng.gen::<f64>()]);
+        let wi = reflect_about(wo, wm);
+
+        if !same_hemisphere(wo, wi) {
+            return None
+        }
+
+        let pdf = self.pdf(surface_interaction, wo, wi);
+        if pdf <= 0.0 {
+            return None
+        }
+
+        Some(BsdfSample {
+            direction: wi,
+            weight: self.eval(surface_interaction, wo, wi) / pdf,
+            pdf,
+            specular: false
+        })
+    }
+
+    fn pdf(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f64 {
+        if self.is_specular() || !same_hemisphere(wo, wi) {
+            return 0.0
+        }
+
+        let wm = wo + wi;
+        if wm.squared_length() == 0.0 {
+            return 0.0
+        }
+        let mut wm = wm.unit();
+        if wm.z < 0.0 {
+            wm = -wm;
+        }
+
+        self.distribution.d_visible(wo, wm) / (4.0 * wo.dot(wm).abs())
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distribution.is_smooth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::checks::*;
+    use crate::material::fresnel_dielectric;
+
+    const ROUGHNESS: [f64; 5] = [0.05, 0.2, 0.4, 0.7, 1.0];
+
+    #[test]
+    fn sample_matches_pdf() {
+        let (eta, k) = complex_ior("gold").unwrap();
+        with_surface(|surface_interaction| {
+            for &roughness in ROUGHNESS.iter() {
+                for &anisotropy in [0.0, 0.8].iter() {
+                    let conductor = Conductor::new(eta, k, roughness, anisotropy);
+                    assert_sample_matches_pdf(&conductor, surface_interaction, 500);
+                    if roughness >= 0.2 {
+                        assert_pdf_integrates(&conductor, surface_interaction, 0.02);
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn eval_is_reciprocal() {
+        let (eta, k) = complex_ior("copper").unwrap();
+        with_surface(|surface_interaction| {
+            for &roughness in ROUGHNESS.iter() {
+                assert_reciprocal(&Conductor::new(eta, k, roughness, 0.0), surface_interaction);
+                assert_reciprocal(&Conductor::new(eta, k, roughness, 0.6), surface_interaction);
+            }
+        });
+    }
+
+    #[test]
+    fn white_furnace() {
+        // A metal reflecting everything only loses light to microfacets shadowing each other, which
+        // leaves 0.91 head on at a roughness of 0.5 and 0.31 at 1
+        let (eta, k) = (Vec3::new(1.0, 1.0, 1.0), Vec3::new(1e4, 1e4, 1e4));
+        with_surface(|surface_interaction| {
+            for &roughness in ROUGHNESS.iter() {
+                let conductor = Conductor::new(eta, k, roughness, 0.0);
+                assert_energy_conserved(&conductor, surface_interaction);
+                if roughness >= 0.2 {
+                    assert_albedo_matches_eval(&conductor, surface_interaction, 0.02);
+                }
+            }
+
+            let head_on = Vec3::new(0.0, 0.0, 1.0);
+            assert!((albedo(&Conductor::new(eta, k, 0.5, 0.0), surface_interaction, head_on, 20_000).x - 0.914).abs() < 0.01);
+            assert!((albedo(&Conductor::new(eta, k, 1.0, 0.0), surface_interaction, head_on, 20_000).x - 0.307).abs() < 0.01);
+        });
+    }
+
+    #[test]
+    fn complex_fresnel() {
+        // Head on the reflectance is ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        let (eta, k) = complex_ior("gold").unwrap();
+        let reflectance = fresnel_conductor(1.0, eta, k);
+        for c in 0..3 {
+            let expected = ((eta[c] - 1.0).powi(2) + k[c] * k[c]) / ((eta[c] + 1.0).powi(2) + k[c] * k[c]);
+            assert!((reflectance[c] - expected).abs() < 1e-9);
+        }
+
+        // Without absorption it is a dielectric, and every metal reflects everything at grazing angles
+        for &cos_theta in [0.1, 0.5, 0.9].iter() {
+            let reflectance = fresnel_conductor(cos_theta, Vec3::new(1.5, 1.5, 1.5), Vec3::new(0.0, 0.0, 0.0));
+            assert!((reflectance.x - fresnel_dielectric(cos_theta, 1.5)).abs() < 1e-9);
+        }
+        assert!(fresnel_conductor(1e-6, eta, k).x > 0.999);
+    }
+}
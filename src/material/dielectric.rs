@@ -0,0 +1,62 @@
+use rand::Rng;
+use crate::material::{Bsdf, BsdfSample};
+use crate::objects::SurfaceInteraction;
+use crate::util::{refract, rng, schlick, Vec3};
+
+// Clear glass which either reflects or refracts, chosen according to the Schlick approximation
+#[derive(Clone)]
+pub struct Dielectric {
+    refraction: f64
+}
+
+impl Dielectric {
+    pub fn new(refraction: f64) -> Dielectric {
+        Dielectric {
+            refraction
+        }
+    }
+}
+
+impl Bsdf for Dielectric {
+    fn eval(&self, _surface_interaction: &SurfaceInteraction, _wo: Vec3, _wi: Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample> {
+        let reflected = Vec3::new(-wo.x, -wo.y, wo.z);
+
+        // The outward normal faces the outgoing direction when light leaves into the outside medium
+        let (normal, ni_over_nt, cosine) = if wo.z > 0.0 {
+            (Vec3::new(0.0, 0.0, 1.0), 1.0 / self.refraction, wo.z)
+        } else {
+            (Vec3::new(0.0, 0.0, -1.0), self.refraction, -wo.z * self.refraction)
+        };
+
+        let direction = match refract(-wo, normal, ni_over_nt) {
+            Some(refracted) => {
+                let reflect_prob = schlick(cosine, self.refraction);
+                if rng().gen::<f64>() < reflect_prob {
+                    reflected
+                } else {
+                    refracted.unit()
+                }
+            }
+            None => reflected
+        };
+
+        Some(BsdfSample {
+            direction,
+            weight: Vec3::new(1.0, 1.0, 1.0),
+            pdf: 0.0,
+            specular: true
+        })
+    }
+
+    fn pdf(&self, _surface_interaction: &SurfaceInteraction, _wo: Vec3, _wi: Vec3) -> f64 {
+        0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
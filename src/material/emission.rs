@@ -0,0 +1,35 @@
+use crate::material::{Bsdf, BsdfSample};
+use crate::objects::SurfaceInteraction;
+use crate::util::Vec3;
+
+// Gives off light equally from both sides and in every direction without reflecting any
+#[derive(Clone)]
+pub struct Emission {
+    color: Vec3
+}
+
+impl Emission {
+    pub fn new(color: Vec3) -> Emission {
+        Emission {
+            color
+        }
+    }
+}
+
+impl Bsdf for Emission {
+    fn eval(&self, _surface_interaction: &SurfaceInteraction, _wo: Vec3, _wi: Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample(&self, _surface_interaction: &SurfaceInteraction, _wo: Vec3) -> Option<BsdfSample> {
+        None
+    }
+
+    fn pdf(&self, _surface_interaction: &SurfaceInteraction, _wo: Vec3, _wi: Vec3) -> f64 {
+        0.0
+    }
+
+    fn emitted(&self) -> Vec3 {
+        self.color
+    }
+}
@@ -0,0 +1,76 @@
+use crate::util::Vec3;
+
+// Unpolarized reflectance of a dielectric interface, where eta is the ratio of the refractive index
+// below the surface to the one above it and cos_theta_i is measured from the side the light comes from
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+
+    // Light arriving from below sees the inverse ratio
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) * 0.5
+}
+
+// Reflectance of a conductor with complex refractive index eta + ik, per color channel
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Vec3, k: Vec3) -> Vec3 {
+    Vec3::new(
+        fresnel_complex(cos_theta_i, eta.x, k.x),
+        fresnel_complex(cos_theta_i, eta.y, k.y),
+        fresnel_complex(cos_theta_i, eta.z, k.z)
+    )
+}
+
+fn fresnel_complex(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta_i.abs().min(1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i.abs() * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    (r_p + r_s) * 0.5
+}
+
+// Refracts the direction about the normal following Snell's law, with both pointing away from the
+// surface and eta as in fresnel_dielectric. Returns the refracted direction and the ratio of indices
+// it crossed, or None on total internal reflection.
+pub fn refract_direction(wi: Vec3, normal: Vec3, eta: f64) -> Option<(Vec3, f64)> {
+    let mut cos_theta_i = normal.dot(wi);
+    let mut eta = eta;
+    let mut normal = normal;
+
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+        normal = -normal;
+    }
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    Some((-wi / eta + normal * (cos_theta_i / eta - cos_theta_t), eta))
+}
@@ -0,0 +1,57 @@
+use crate::material::{same_hemisphere, Bsdf, BsdfSample};
+use crate::objects::SurfaceInteraction;
+use crate::texture::Texture;
+use crate::util::{rng, Vec3};
+use std::sync::Arc;
+
+// Ideal diffuse reflection on both sides of the surface
+#[derive(Clone)]
+pub struct Lambertian {
+    attenuation: Arc<dyn Texture>
+}
+
+impl Lambertian {
+    pub fn new(attenuation: Arc<dyn Texture>) -> Lambertian {
+        Lambertian {
+            attenuation
+        }
+    }
+}
+
+impl Bsdf for Lambertian {
+    fn eval(&self, surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> Vec3 {
+        if same_hemisphere(wo, wi) {
+            self.attenuation.value(surface_interaction.uv, surface_interaction.point) * (wi.z.abs() / std::f64::consts::PI)
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn sample(&self, surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample> {
+        // Offsetting a point on the unit sphere by the normal gives a cosine weighted direction
+        let mut wi = (Vec3::new(0.0, 0.0, 1.0) + Vec3::random_in_unit_sphere(&mut rng()).unit()).unit();
+        if wo.z < 0.0 {
+            wi.z = -wi.z;
+        }
+
+        let pdf = self.pdf(surface_interaction, wo, wi);
+        if pdf <= 0.0 {
+            return None
+        }
+
+        Some(BsdfSample {
+            direction: wi,
+            weight: self.attenuation.value(surface_interaction.uv, surface_interaction.point),
+            pdf,
+            specular: false
+        })
+    }
+
+    fn pdf(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f64 {
+        if same_hemisphere(wo, wi) {
+            wi.z.abs() / std::f64::consts::PI
+        } else {
+            0.0
+        }
+    }
+}
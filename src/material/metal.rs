@@ -0,0 +1,122 @@
+use rand::Rng;
+use crate::material::{same_hemisphere, Bsdf, BsdfSample};
+use crate::objects::SurfaceInteraction;
+use crate::texture::Texture;
+use crate::util::{coordinate_system, rng, Vec3};
+use std::sync::Arc;
+
+// Mirror reflection, spread over a cosine power lobe around the mirror direction when rough
+#[derive(Clone)]
+pub struct Metal {
+    attenuation: Arc<dyn Texture>,
+    fuzziness: f64
+}
+
+impl Metal {
+    pub fn new(attenuation: Arc<dyn Texture>, fuzziness: f64) -> Metal {
+        Metal {
+            attenuation,
+            fuzziness
+        }
+    }
+
+    // Exponent of the lobe giving roughly the same spread as the fuzziness
+    fn exponent(&self) -> f64 {
+        let fuzziness = self.fuzziness.min(1.0);
+        2.0 / (fuzziness * fuzziness) - 2.0
+    }
+}
+
+fn mirror(wo: Vec3) -> Vec3 {
+    Vec3::new(-wo.x, -wo.y, wo.z)
+}
+
+impl Bsdf for Metal {
+    // Unlike the other BSDFs this lobe is defined by how it is sampled rather than by a model of
+    // the surface: every direction it picks above the surface carries the attenuation, and those
+    // below it are absorbed. Evaluating it is then a matter of scaling its density. Sampling and
+    // evaluation agree, so it mixes with light sampling, but the BSDF is not reciprocal and the
+    // highlight has no Fresnel or shadowing; Conductor is the physically based alternative.
+    fn eval(&self, surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.attenuation.value(surface_interaction.uv, surface_interaction.point) * self.pdf(surface_interaction, wo, wi)
+    }
+
+    fn sample(&self, surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample> {
+        let reflected = mirror(wo);
+
+        let wi = if self.is_specular() {
+            reflected
+        } else {
+            let mut rng = rng();
+            let cos_theta = rng.gen::<f64>().powf(1.0 / (self.exponent() + 1.0));
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+            let (u, v) = coordinate_system(reflected);
+            u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + reflected * cos_theta
+        };
+
+        // Check to make sure the ray is not reflecting into the surface
+        if !same_hemisphere(wo, wi) {
+            return None
+        }
+
+        Some(BsdfSample {
+            direction: wi,
+            weight: self.attenuation.value(surface_interaction.uv, surface_interaction.point),
+            pdf: if self.is_specular() { 0.0 } else { self.pdf(surface_interaction, wo, wi) },
+            specular: self.is_specular()
+        })
+    }
+
+    fn pdf(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f64 {
+        if self.is_specular() || !same_hemisphere(wo, wi) {
+            return 0.0
+        }
+
+        let exponent = self.exponent();
+        let cosine = mirror(wo).dot(wi);
+
+        if cosine > 0.0 {
+            (exponent + 1.0) / (2.0 * std::f64::consts::PI) * cosine.powf(exponent)
+        } else {
+            0.0
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        self.fuzziness <= 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::checks::*;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn sample_matches_pdf() {
+        with_surface(|surface_interaction| {
+            for &fuzziness in [0.1, 0.5, 1.0].iter() {
+                let metal = Metal::new(Arc::new(ConstantTexture::new(Vec3::new(0.9, 0.6, 0.3))), fuzziness);
+                assert_sample_matches_pdf(&metal, surface_interaction, 1000);
+                assert_pdf_integrates(&metal, surface_interaction, 0.02);
+                assert_albedo_matches_eval(&metal, surface_interaction, 0.02);
+                assert_energy_conserved(&metal, surface_interaction);
+            }
+        });
+    }
+
+    #[test]
+    fn mirror_is_specular() {
+        with_surface(|surface_interaction| {
+            let metal = Metal::new(Arc::new(ConstantTexture::new(Vec3::new(0.9, 0.6, 0.3))), 0.0);
+            let wo = Vec3::new(0.6, 0.0, 0.8);
+            let sample = metal.sample(surface_interaction, wo).unwrap();
+            assert!(sample.specular);
+            assert_eq!([sample.direction.x, sample.direction.y, sample.direction.z], [-0.6, 0.0, 0.8]);
+            assert_eq!([sample.weight.x, sample.weight.y, sample.weight.z], [0.9, 0.6, 0.3]);
+            assert_eq!(metal.pdf(surface_interaction, wo, sample.direction), 0.0);
+        });
+    }
+}
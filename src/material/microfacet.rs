@@ -0,0 +1,101 @@
+use crate::util::Vec3;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals in the local shading frame, with a
+// separate width along the tangent and bitangent for anisotropic surfaces
+#[derive(Copy, Clone)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64
+}
+
+impl TrowbridgeReitz {
+    // Roughness is squared into the distribution width so it reads perceptually linear, and
+    // anisotropy in [0, 1) stretches the highlight along the tangent
+    pub fn new(roughness: f64, anisotropy: f64) -> TrowbridgeReitz {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+
+        TrowbridgeReitz {
+            alpha_x: (alpha / aspect).max(1e-4),
+            alpha_y: (alpha * aspect).max(1e-4)
+        }
+    }
+
+    // Close enough to a perfect mirror that it should be treated as specular
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // Density of microfacets facing wm
+    pub fn d(&self, wm: Vec3) -> f64 {
+        let cos2_theta = wm.z * wm.z;
+        if cos2_theta < 1e-16 {
+            return 0.0
+        }
+
+        let e = ((wm.x / self.alpha_x).powi(2) + (wm.y / self.alpha_y).powi(2)) / cos2_theta;
+        1.0 / (std::f64::consts::PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * (1.0 + e) * (1.0 + e))
+    }
+
+    // Smith auxiliary function, the ratio of shadowed to visible microfacet area for direction w
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        if w.z * w.z < 1e-16 {
+            return 0.0
+        }
+
+        let alpha2_tan2_theta = ((w.x * self.alpha_x).powi(2) + (w.y * self.alpha_y).powi(2)) / (w.z * w.z);
+        ((1.0 + alpha2_tan2_theta).sqrt() - 1.0) * 0.5
+    }
+
+    // Fraction of microfacets visible from w
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated fraction of microfacets visible from both directions
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the microfacet normals visible from w, which is what `sample_visible` draws from
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0
+        }
+
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // Samples a microfacet normal visible from w (Heitz 2018), always in the upper hemisphere
+    pub fn sample_visible(&self, w: Vec3, u: [f64; 2]) -> Vec3 {
+        // Stretch the view direction so the distribution becomes a hemisphere
+        let mut wh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).unit();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+
+        let t1 = if wh.z < 0.99999 {
+            Vec3::new(0.0, 0.0, 1.0).cross(wh).unit()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        // Uniform point on a disk, squeezed onto the part of the hemisphere visible from wh
+        let r = u[0].sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u[1];
+        let px = r * phi.cos();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z) * 0.5;
+        let py = (1.0 - s) * h + s * r * phi.sin();
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let nh = t1 * px + t2 * py + wh * pz;
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).unit()
+    }
+}
+
+// Reflects w about the normal n, both pointing away from the surface
+pub fn reflect_about(w: Vec3, n: Vec3) -> Vec3 {
+    n * (2.0 * w.dot(n)) - w
+}
@@ -1,85 +1,91 @@
-use rand::{thread_rng, Rng};
-use crate::util::{reflect, refract, schlick, Vec3, Ray};
+use crate::objects::SurfaceInteraction;
+use crate::util::{Vec3, Ray};
+use std::sync::Arc;
 
-#[derive(Copy, Clone)]
+pub mod bsdf;
+pub mod fresnel;
+pub mod microfacet;
+pub mod lambertian;
+pub mod metal;
+pub mod conductor;
+pub mod dielectric;
+pub mod rough_dielectric;
+pub mod principled;
+pub mod emission;
+
+pub use bsdf::*;
+pub use fresnel::*;
+pub use microfacet::*;
+pub use lambertian::Lambertian;
+pub use metal::Metal;
+pub use conductor::{complex_ior, Conductor};
+pub use dielectric::Dielectric;
+pub use rough_dielectric::RoughDielectric;
+pub use principled::Principled;
+pub use emission::Emission;
+
+#[derive(Clone)]
 pub enum Material {
-    Lambertian {
-        attenuation: Vec3
-    },
-    Metal {
-        attenuation: Vec3,
-        fuzziness: f64
-    },
-    Dielectric {
-        refraction: f64
-    },
-    Emission {
-        color: Vec3
-    }
+    Lambertian(Lambertian),
+    Metal(Metal),
+    Conductor(Conductor),
+    Dielectric(Dielectric),
+    RoughDielectric(RoughDielectric),
+    Principled(Principled),
+    Emission(Emission),
+    // Any other BSDF, such as one implemented outside this crate
+    Custom(Arc<dyn Bsdf>)
 }
 
 impl Material {
-    pub fn scatter(&self, r: Ray, n: Vec3, p: Vec3) -> (Vec3, Option<Ray>) {
+    pub fn bsdf(&self) -> &dyn Bsdf {
         match self {
-            Material::Lambertian {
-                attenuation
-            } => {
-                let target = p + n + Vec3::random_in_unit_sphere(thread_rng());
-                (*attenuation, Some(Ray::new(p, target - p)))
-            }
-            Material::Metal {
-                attenuation,
-                fuzziness
-            } => {
-                let reflected = reflect(r.direction.unit(), n);
-                let scattered = Ray::new(p, reflected + Vec3::random_in_unit_sphere(thread_rng()) * *fuzziness);
+            Material::Lambertian(bsdf) => bsdf,
+            Material::Metal(bsdf) => bsdf,
+            Material::Conductor(bsdf) => bsdf,
+            Material::Dielectric(bsdf) => bsdf,
+            Material::RoughDielectric(bsdf) => bsdf,
+            Material::Principled(bsdf) => bsdf,
+            Material::Emission(bsdf) => bsdf,
+            Material::Custom(bsdf) => bsdf.as_ref()
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        let emitted = self.emitted();
+        emitted.x > 0.0 || emitted.y > 0.0 || emitted.z > 0.0
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.bsdf().is_specular()
+    }
 
-                // Check to make sure the ray is not reflecting in the same direction
-                if scattered.direction.dot(n) > 0.0 {
-                    (*attenuation, Some(scattered))
-                } else {
-                    (Vec3::new(0.0, 0.0, 0.0), None)
-                }
-            }
-            Material::Dielectric {
-                refraction
-            } => {
-                let reflected = reflect(r.direction, n);
+    pub fn emitted(&self) -> Vec3 {
+        self.bsdf().emitted()
+    }
 
-                let (outward_normal, ni_over_nt, cosine) = if r.direction.dot(n) > 0.0 {
-                    (
-                        -n,
-                        *refraction,
-                        *refraction * r.direction.dot(n) / r.direction.length()
-                    )
-                } else {
-                    (
-                        n,
-                        1.0 / *refraction,
-                        -(r.direction.dot(n)) / r.direction.length()
-                    )
-                };
+    // The methods below work in world space, with the ray arriving at the surface and the
+    // direction pointing away from it towards where light arrives from
 
-                let scattered = match refract(r.direction, outward_normal, ni_over_nt) {
-                    Some(refracted) => {
-                        let reflect_prob = schlick(cosine, *refraction);
-                        let mut rng = thread_rng();
-                        if rng.gen::<f64>() < reflect_prob {
-                            Ray::new(p, reflected)
-                        } else {
-                            Ray::new(p, refracted)
-                        }
-                    }
-                    None => Ray::new(p, reflected)
-                };
+    pub fn eval(&self, r: Ray, surface_interaction: &SurfaceInteraction, direction: Vec3) -> Vec3 {
+        let wo = surface_interaction.to_local(-r.direction.unit());
+        let wi = surface_interaction.to_local(direction.unit());
+        self.bsdf().eval(surface_interaction, wo, wi)
+    }
 
-                (Vec3::new(1.0, 1.0, 1.0), Some(scattered))
-            }
-            Material::Emission {
-                color
-            } => {
-                (*color, None)
-            }
-        }
+    pub fn pdf(&self, r: Ray, surface_interaction: &SurfaceInteraction, direction: Vec3) -> f64 {
+        let wo = surface_interaction.to_local(-r.direction.unit());
+        let wi = surface_interaction.to_local(direction.unit());
+        self.bsdf().pdf(surface_interaction, wo, wi)
     }
-}
\ No newline at end of file
+
+    pub fn sample(&self, r: Ray, surface_interaction: &SurfaceInteraction) -> Option<BsdfSample> {
+        let wo = surface_interaction.to_local(-r.direction.unit());
+        let sample = self.bsdf().sample(surface_interaction, wo)?;
+
+        Some(BsdfSample {
+            direction: surface_interaction.to_world(sample.direction),
+            ..sample
+        })
+    }
+}
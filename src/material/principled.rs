@@ -0,0 +1,372 @@
+use rand::Rng;
+use crate::material::{reflect_about, same_hemisphere, Bsdf, BsdfSample, RoughDielectric, TrowbridgeReitz};
+use crate::objects::SurfaceInteraction;
+use crate::texture::{ConstantTexture, Texture};
+use crate::util::{rng, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Roughness is kept above this so every lobe has a density that can be evaluated
+const MIN_ROUGHNESS: f64 = 0.04;
+
+// Layered material after the Disney principled BRDF (Burley 2012, 2015). A diffuse base with sheen
+// and a subsurface approximation is blended with a metallic or dielectric specular layer and rough
+// glass for transmission, all under an optional clearcoat. Scalar parameters are read from the first
+// channel of their textures and run from 0 to 1. Fields are public so that a material can be made
+// from `new` with a few of them replaced.
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    // Strength of the dielectric highlight, where 0.5 is a reflectance of 4% at normal incidence
+    pub specular: Arc<dyn Texture>,
+    pub sheen: Arc<dyn Texture>,
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_roughness: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub subsurface: Arc<dyn Texture>,
+    // Refractive index used by the transmission
+    pub refraction: f64
+}
+
+impl Principled {
+    pub fn new(base_color: Arc<dyn Texture>) -> Principled {
+        let constant = |value| -> Arc<dyn Texture> { Arc::new(ConstantTexture::new(Vec3::new(value, value, value))) };
+
+        Principled {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_roughness: constant(0.03),
+            transmission: constant(0.0),
+            subsurface: constant(0.0),
+            refraction: 1.5
+        }
+    }
+
+    fn parameters(&self, surface_interaction: &SurfaceInteraction) -> Parameters {
+        let (uv, p) = (surface_interaction.uv, surface_interaction.point);
+        let scalar = |texture: &Arc<dyn Texture>| texture.value(uv, p).x.clamp(0.0, 1.0);
+
+        let base_color = self.base_color.value(uv, p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness).max(MIN_ROUGHNESS);
+        let transmission = scalar(&self.transmission);
+        let clearcoat = scalar(&self.clearcoat);
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let glass_weight = (1.0 - metallic) * transmission;
+        let specular_weight = 1.0 - glass_weight;
+        let clearcoat_weight = 0.25 * clearcoat;
+
+        // Lobes are picked roughly in proportion to how much light they reflect
+        let probabilities = [
+            diffuse_weight * luminance(base_color).max(0.05),
+            specular_weight * (0.25 + 0.75 * metallic),
+            glass_weight,
+            clearcoat_weight
+        ];
+        let total: f64 = probabilities.iter().sum();
+
+        Parameters {
+            base_color,
+            metallic,
+            roughness,
+            specular: scalar(&self.specular),
+            sheen: scalar(&self.sheen),
+            subsurface: scalar(&self.subsurface),
+            diffuse_weight,
+            specular_weight,
+            glass_weight,
+            clearcoat_weight,
+            probabilities: [probabilities[0] / total, probabilities[1] / total, probabilities[2] / total, probabilities[3] / total],
+            distribution: TrowbridgeReitz::new(roughness, 0.0),
+            clearcoat_distribution: TrowbridgeReitz::new(scalar(&self.clearcoat_roughness).max(MIN_ROUGHNESS), 0.0),
+            glass: RoughDielectric::new(self.refraction, roughness)
+        }
+    }
+}
+
+// Parameters looked up at a single point along with the weight of each lobe
+struct Parameters {
+    base_color: Vec3,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    sheen: f64,
+    subsurface: f64,
+    diffuse_weight: f64,
+    specular_weight: f64,
+    glass_weight: f64,
+    clearcoat_weight: f64,
+    // Chance of sampling the diffuse, specular, glass and clearcoat lobes
+    probabilities: [f64; 4],
+    distribution: TrowbridgeReitz,
+    clearcoat_distribution: TrowbridgeReitz,
+    glass: RoughDielectric
+}
+
+fn luminance(c: Vec3) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+fn mix(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    a * (1.0 - t) + b * t
+}
+
+fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+// Mirrors both directions into the upper hemisphere, so the reflection lobes work the same on both sides
+fn upper(wo: Vec3, wi: Vec3) -> (Vec3, Vec3) {
+    if wo.z < 0.0 {
+        (Vec3::new(wo.x, wo.y, -wo.z), Vec3::new(wi.x, wi.y, -wi.z))
+    } else {
+        (wo, wi)
+    }
+}
+
+// Share of the light crossing the clearcoat at an angle that it does not reflect
+fn coat_transmittance(p: &Parameters, cosine: f64) -> f64 {
+    1.0 - p.clearcoat_weight * (0.04 + 0.96 * schlick_weight(cosine))
+}
+
+fn reflection_pdf(distribution: &TrowbridgeReitz, wo: Vec3, wh: Vec3) -> f64 {
+    distribution.d_visible(wo, wh) / (4.0 * wo.dot(wh).abs())
+}
+
+impl Principled {
+    // Diffuse, sheen, specular and clearcoat reflection, with both directions in the upper hemisphere
+    fn eval_reflection(&self, p: &Parameters, wo: Vec3, wi: Vec3) -> Vec3 {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let (cos_o, cos_i) = (wo.z, wi.z);
+
+        let wh = wo + wi;
+        if cos_o <= 0.0 || cos_i <= 0.0 || wh.squared_length() == 0.0 {
+            return black
+        }
+        let wh = wh.unit();
+        let cos_d = wi.dot(wh);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+
+        let mut value = black;
+
+        // Light reaching a layer and leaving it again passes twice through those above it, which
+        // keeps the layers from reflecting more than arrives between them
+        let under_coat = coat_transmittance(p, cos_i) * coat_transmittance(p, cos_o);
+        let dielectric_transmittance = |cos: f64| (1.0 - 0.08 * p.specular) * (1.0 - schlick_weight(cos));
+        let under_specular = dielectric_transmittance(cos_i) * dielectric_transmittance(cos_o);
+
+        if p.diffuse_weight > 0.0 {
+            let (fl, fv) = (schlick_weight(cos_i), schlick_weight(cos_o));
+
+            // Burley diffuse with its retro-reflection at grazing angles
+            let rr = 2.0 * p.roughness * cos_d * cos_d;
+            let lambert = (1.0 - 0.5 * fl) * (1.0 - 0.5 * fv);
+            let retro = rr * (fl + fv + fl * fv * (rr - 1.0));
+
+            // Hanrahan-Krueger approximation of subsurface scattering
+            let fss90 = cos_d * cos_d * p.roughness;
+            let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+            let subsurface = 1.25 * (fss * (1.0 / (cos_i + cos_o) - 0.5) + 0.5);
+
+            let diffuse = p.base_color * ((lambert * (1.0 - p.subsurface) + subsurface * p.subsurface + retro) / PI * under_specular);
+            let sheen = white * (p.sheen * schlick_weight(cos_d));
+            value += (diffuse + sheen) * (p.diffuse_weight * under_coat * cos_i);
+        }
+
+        if p.specular_weight > 0.0 {
+            let dielectric = white * (0.08 * p.specular);
+            let f0 = mix(dielectric, p.base_color, p.metallic);
+            let fresnel = mix(f0, white, schlick_weight(cos_d));
+            value += fresnel * (p.specular_weight * under_coat * p.distribution.d(wh) * p.distribution.g(wo, wi) / (4.0 * cos_o));
+        }
+
+        if p.clearcoat_weight > 0.0 {
+            let fresnel = 0.04 + 0.96 * schlick_weight(cos_d);
+            let distribution = &p.clearcoat_distribution;
+            value += white * (p.clearcoat_weight * fresnel * distribution.d(wh) * distribution.g(wo, wi) / (4.0 * cos_o));
+        }
+
+        value
+    }
+
+    fn pdf_parameters(&self, surface_interaction: &SurfaceInteraction, p: &Parameters, wo: Vec3, wi: Vec3) -> f64 {
+        let mut pdf = 0.0;
+
+        if same_hemisphere(wo, wi) {
+            let (wo, wi) = upper(wo, wi);
+            let wh = wo + wi;
+
+            pdf += p.probabilities[0] * wi.z / PI;
+
+            if wh.squared_length() > 0.0 {
+                let wh = wh.unit();
+                pdf += p.probabilities[1] * reflection_pdf(&p.distribution, wo, wh);
+                pdf += p.probabilities[3] * reflection_pdf(&p.clearcoat_distribution, wo, wh);
+            }
+        }
+
+        if p.probabilities[2] > 0.0 {
+            pdf += p.probabilities[2] * p.glass.pdf(surface_interaction, wo, wi);
+        }
+
+        pdf
+    }
+
+    fn eval_parameters(&self, surface_interaction: &SurfaceInteraction, p: &Parameters, wo: Vec3, wi: Vec3) -> Vec3 {
+        let mut value = Vec3::new(0.0, 0.0, 0.0);
+
+        if same_hemisphere(wo, wi) {
+            let (wo, wi) = upper(wo, wi);
+            value += self.eval_reflection(p, wo, wi);
+        }
+
+        if p.glass_weight > 0.0 {
+            let under_coat = coat_transmittance(p, wo.z.abs()) * coat_transmittance(p, wi.z.abs());
+            let glass = p.glass.eval(surface_interaction, wo, wi) * (p.glass_weight * under_coat);
+
+            // Light passing through is tinted by the base color
+            value += if same_hemisphere(wo, wi) { glass } else { glass * p.base_color };
+        }
+
+        value
+    }
+}
+
+impl Bsdf for Principled {
+    fn eval(&self, surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> Vec3 {
+        let p = self.parameters(surface_interaction);
+        self.eval_parameters(surface_interaction, &p, wo, wi)
+    }
+
+    fn sample(&self, surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None
+        }
+
+        let p = self.parameters(surface_interaction);
+        let mut rng = rng();
+        let u = rng.gen::<f64>();
+        let flip = |w: Vec3| if wo.z < 0.0 { Vec3::new(w.x, w.y, -w.z) } else { w };
+        let wo_upper = flip(wo);
+
+        let wi = if u < p.probabilities[0] {
+            let wi = (Vec3::new(0.0, 0.0, 1.0) + Vec3::random_in_unit_sphere(&mut rng).unit()).unit();
+            flip(wi)
+        } else if u < p.probabilities[0] + p.probabilities[1] {
+            let wh = p.distribution.sample_visible(wo_upper, [rng.gen::<f64>(), rng.gen::<f64>()]);
+            flip(reflect_about(wo_upper, wh))
+        } else if u < p.probabilities[0] + p.probabilities[1] + p.probabilities[2] {
+            p.glass.sample(surface_interaction, wo)?.direction
+        } else {
+            let wh = p.clearcoat_distribution.sample_visible(wo_upper, [rng.gen::<f64>(), rng.gen::<f64>()]);
+            flip(reflect_about(wo_upper, wh))
+        };
+
+        let pdf = self.pdf_parameters(surface_interaction, &p, wo, wi);
+        if pdf <= 0.0 || wi.z == 0.0 {
+            return None
+        }
+
+        Some(BsdfSample {
+            direction: wi,
+            weight: self.eval_parameters(surface_interaction, &p, wo, wi) / pdf,
+            pdf,
+            specular: false
+        })
+    }
+
+    fn pdf(&self, surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f64 {
+        let p = self.parameters(surface_interaction);
+        self.pdf_parameters(surface_interaction, &p, wo, wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::checks::*;
+    use crate::util::seed_rng;
+
+    fn constant(value: f64) -> Arc<dyn Texture> {
+        Arc::new(ConstantTexture::new(Vec3::new(value, value, value)))
+    }
+
+    // Each of metallic, transmission and clearcoat at either end, over the given roughness values.
+    // A metal does not transmit, so it is only made once.
+    fn materials(base_color: f64, roughness: &[f64], clearcoat_roughness: f64) -> Vec<(String, Principled)> {
+        let mut materials = vec![];
+        for &metallic in [0.0, 1.0].iter() {
+            for &transmission in [0.0, 1.0].iter().filter(|&&transmission| metallic == 0.0 || transmission == 0.0) {
+                for &clearcoat in [0.0, 1.0].iter() {
+                    for &roughness in roughness.iter() {
+                        let mut principled = Principled::new(constant(base_color));
+                        principled.metallic = constant(metallic);
+                        principled.transmission = constant(transmission);
+                        principled.clearcoat = constant(clearcoat);
+                        principled.clearcoat_roughness = constant(clearcoat_roughness);
+                        principled.roughness = constant(roughness);
+
+                        let name = format!("metallic {}, transmission {}, clearcoat {}, roughness {}", metallic, transmission, clearcoat, roughness);
+                        materials.push((name, principled));
+                    }
+                }
+            }
+        }
+        materials
+    }
+
+    #[test]
+    fn sample_matches_pdf() {
+        with_surface(|surface_interaction| {
+            // Roughness below the minimum is clamped to it
+            for (_, principled) in materials(0.7, &[0.0, 0.5, 1.0], 0.03) {
+                assert_sample_matches_pdf(&principled, surface_interaction, 300);
+            }
+            for (_, principled) in materials(0.7, &[0.5], 0.5) {
+                assert_pdf_integrates(&principled, surface_interaction, 0.02);
+            }
+        });
+    }
+
+    #[test]
+    fn reflection_is_reciprocal() {
+        with_surface(|surface_interaction| {
+            for (_, mut principled) in materials(0.7, &[0.3, 1.0], 0.3) {
+                // The sheen and the Schlick weights of the diffuse lobe are symmetric too, but the
+                // retro-reflection only once both are in
+                principled.sheen = constant(0.5);
+                assert_reciprocal(&principled, surface_interaction);
+            }
+        });
+    }
+
+    #[test]
+    fn white_furnace() {
+        with_surface(|surface_interaction| {
+            // As for the rough dielectric, light refracted through the glass lobe is counted
+            // without the scaling of its radiance by the ratio of indices
+            for (name, principled) in materials(1.0, &[0.0, 0.5, 1.0], 0.03) {
+                seed_rng(4);
+                for wo in outgoing() {
+                    let etap = if wo.z > 0.0 { principled.refraction } else { 1.0 / principled.refraction };
+                    let samples = 20_000;
+                    let energy = (0..samples)
+                        .filter_map(|_| principled.sample(surface_interaction, wo))
+                        .map(|sample| if same_hemisphere(wo, sample.direction) { sample.weight.x } else { sample.weight.x * etap * etap })
+                        .sum::<f64>() / samples as f64;
+                    assert!(energy <= 1.01, "{}, wo {:?}: energy {}", name, [wo.x, wo.y, wo.z], energy);
+                }
+            }
+            for (_, principled) in materials(1.0, &[0.5], 0.5) {
+                assert_albedo_matches_eval(&principled, surface_interaction, 0.02);
+            }
+        });
+    }
+}
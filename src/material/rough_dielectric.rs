@@ -0,0 +1,234 @@
+use rand::Rng;
+use crate::material::{fresnel_dielectric, reflect_about, refract_direction, same_hemisphere, Bsdf, BsdfSample, TrowbridgeReitz};
+use crate::objects::SurfaceInteraction;
+use crate::util::{rng, Vec3};
+
+// Glass with microfacet roughness (Walter et al. 2007), such as frosted or etched glass. The
+// refractive index is that of the inside, taken to be the side the normal points away from.
+#[derive(Clone)]
+pub struct RoughDielectric {
+    refraction: f64,
+    distribution: TrowbridgeReitz
+}
+
+impl RoughDielectric {
+    pub fn new(refraction: f64, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            refraction,
+            distribution: TrowbridgeReitz::new(roughness, 0.0)
+        }
+    }
+
+    // The microfacet normal which scatters wo into wi, facing up, along with the ratio of refractive
+    // indices crossed and whether this is a reflection. None when no visible microfacet can do it.
+    fn half_vector(&self, wo: Vec3, wi: Vec3) -> Option<(Vec3, f64, bool)> {
+        if wo.z == 0.0 || wi.z == 0.0 {
+            return None
+        }
+
+        let reflection = same_hemisphere(wo, wi);
+        let etap = if reflection {
+            1.0
+        } else if wo.z > 0.0 {
+            self.refraction
+        } else {
+            1.0 / self.refraction
+        };
+
+        let wm = wi * etap + wo;
+        if wm.squared_length() == 0.0 {
+            return None
+        }
+        let mut wm = wm.unit();
+        if wm.z < 0.0 {
+            wm = -wm;
+        }
+
+        // Microfacets seen from behind cannot contribute
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None
+        }
+
+        Some((wm, etap, reflection))
+    }
+}
+
+impl Bsdf for RoughDielectric {
+    fn eval(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> Vec3 {
+        if self.is_specular() {
+            return Vec3::new(0.0, 0.0, 0.0)
+        }
+
+        let (wm, etap, reflection) = match self.half_vector(wo, wi) {
+            Some(half_vector) => half_vector,
+            None => return Vec3::new(0.0, 0.0, 0.0)
+        };
+
+        let fresnel = fresnel_dielectric(wo.dot(wm), self.refraction);
+        let dg = self.distribution.d(wm) * self.distribution.g(wo, wi);
+
+        let value = if reflection {
+            dg * fresnel / (4.0 * wo.z.abs())
+        } else {
+            // Radiance is compressed into a smaller solid angle when entering a denser medium
+            let denominator = (wi.dot(wm) + wo.dot(wm) / etap).powi(2) * wo.z;
+            dg * (1.0 - fresnel) * (wi.dot(wm) * wo.dot(wm) / denominator).abs() / (etap * etap)
+        };
+        Vec3::new(value, value, value)
+    }
+
+    fn sample(&self, surface_interaction: &SurfaceInteraction, wo: Vec3) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None
+        }
+
+        let mut rng = rng();
+
+        if self.is_specular() {
+            let reflectance = fresnel_dielectric(wo.z, self.refraction);
+
+            return if rng.gen::<f64>() < reflectance {
+                Some(BsdfSample {
+                    direction: Vec3::new(-wo.x, -wo.y, wo.z),
+                    weight: Vec3::new(1.0, 1.0, 1.0),
+                    pdf: 0.0,
+                    specular: true
+                })
+            } else {
+                let (wi, etap) = refract_direction(wo, Vec3::new(0.0, 0.0, 1.0), self.refraction)?;
+                let scale = 1.0 / (etap * etap);
+
+                Some(BsdfSample {
+                    direction: wi,
+                    weight: Vec3::new(scale, scale, scale),
+                    pdf: 0.0,
+                    specular: true
+                })
+            }
+        }
+
+        let wm = self.distribution.sample_visible(wo, [rng.gen::<f64>(), rng.gen::<f64>()]);
+        let reflectance = fresnel_dielectric(wo.dot(wm), self.refraction);
+
+        let wi = if rng.gen::<f64>() < reflectance {
+            let wi = reflect_about(wo, wm);
+            if !same_hemisphere(wo, wi) {
+                return None
+            }
+            wi
+        } else {
+            let (wi, _) = refract_direction(wo, wm, self.refraction)?;
+            if same_hemisphere(wo, wi) || wi.z == 0.0 {
+                return None
+            }
+            wi
+        };
+
+        let pdf = self.pdf(surface_interaction, wo, wi);
+        if pdf <= 0.0 {
+            return None
+        }
+
+        Some(BsdfSample {
+            direction: wi,
+            weight: self.eval(surface_interaction, wo, wi) / pdf,
+            pdf,
+            specular: false
+        })
+    }
+
+    fn pdf(&self, _surface_interaction: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f64 {
+        if self.is_specular() {
+            return 0.0
+        }
+
+        let (wm, etap, reflection) = match self.half_vector(wo, wi) {
+            Some(half_vector) => half_vector,
+            None => return 0.0
+        };
+
+        let reflectance = fresnel_dielectric(wo.dot(wm), self.refraction);
+        let visible = self.distribution.d_visible(wo, wm);
+
+        if reflection {
+            visible / (4.0 * wo.dot(wm).abs()) * reflectance
+        } else {
+            let denominator = (wi.dot(wm) + wo.dot(wm) / etap).powi(2);
+            visible * wi.dot(wm).abs() / denominator * (1.0 - reflectance)
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distribution.is_smooth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::checks::*;
+    use crate::util::seed_rng;
+
+    const ROUGHNESS: [f64; 4] = [0.1, 0.3, 0.6, 1.0];
+
+    #[test]
+    fn sample_matches_pdf() {
+        with_surface(|surface_interaction| {
+            for &roughness in ROUGHNESS.iter() {
+                let glass = RoughDielectric::new(1.5, roughness);
+                assert_sample_matches_pdf(&glass, surface_interaction, 500);
+                if roughness >= 0.3 {
+                    assert_pdf_integrates(&glass, surface_interaction, 0.02);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn eval_is_reciprocal() {
+        with_surface(|surface_interaction| {
+            for &roughness in ROUGHNESS.iter() {
+                let glass = RoughDielectric::new(1.5, roughness);
+                assert_reciprocal(&glass, surface_interaction);
+
+                // Radiance grows by the square of the ratio of indices on entering the denser side, so
+                // it is the BSDF divided by the square of the index on the side of wo that is symmetric
+                let index = |w: Vec3| if w.z > 0.0 { 1.0 } else { 1.5 };
+                let directions = sphere_directions(300);
+                for &wo in directions.iter() {
+                    for &wi in directions.iter().filter(|wi| !same_hemisphere(wo, **wi)) {
+                        let forward = glass.eval(surface_interaction, wo, wi).x / wi.z.abs() / (index(wo) * index(wo));
+                        let backward = glass.eval(surface_interaction, wi, wo).x / wo.z.abs() / (index(wi) * index(wi));
+                        assert!((forward - backward).abs() <= 1e-6 * forward.max(backward), "{} against {}", forward, backward);
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn white_furnace() {
+        with_surface(|surface_interaction| {
+            for &roughness in ROUGHNESS.iter() {
+                let glass = RoughDielectric::new(1.5, roughness);
+                if roughness >= 0.3 {
+                    assert_albedo_matches_eval(&glass, surface_interaction, 0.02);
+                }
+
+                // The energy leaving is the radiance carried over to the side of wo, which leaves out
+                // the scaling by the ratio of indices
+                seed_rng(4);
+                for wo in outgoing() {
+                    let etap = if wo.z > 0.0 { 1.5 } else { 1.0 / 1.5 };
+                    let samples = 20_000;
+                    let energy = (0..samples)
+                        .filter_map(|_| glass.sample(surface_interaction, wo))
+                        .map(|sample| if same_hemisphere(wo, sample.direction) { sample.weight.x } else { sample.weight.x * etap * etap })
+                        .sum::<f64>() / samples as f64;
+                    assert!(energy <= 1.01, "roughness {}, wo {:?}: energy {}", roughness, [wo.x, wo.y, wo.z], energy);
+                    assert!(energy > 0.25, "roughness {}, wo {:?}: energy {}", roughness, [wo.x, wo.y, wo.z], energy);
+                }
+            }
+        });
+    }
+}
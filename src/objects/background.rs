@@ -0,0 +1,289 @@
+use rand::Rng;
+use crate::objects::LightSample;
+use crate::texture::load_radiance;
+use crate::util::{rng, Distribution2D, Vec3};
+use std::f64::consts::PI;
+use std::path::Path;
+
+// Light arriving from infinitely far away, seen by rays that leave the scene without hitting anything
+pub trait Background: Send + Sync {
+    // Radiance arriving along the unit direction, which points away from the scene
+    fn radiance(&self, direction: Vec3) -> Vec3;
+
+    // Backgrounds that return true are sampled directly by the integrator alongside the emitters
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // Picks a direction to gather light from, the same from every point in the scene
+    fn sample_light(&self) -> Option<LightSample> {
+        None
+    }
+
+    // Density with respect to solid angle of `sample_light` picking the direction
+    fn pdf_value(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+}
+
+// The same color in every direction, such as black for a scene lit only by its emitters
+pub struct ConstantBackground {
+    color: Vec3
+}
+
+impl ConstantBackground {
+    pub fn new(color: Vec3) -> ConstantBackground {
+        ConstantBackground {
+            color
+        }
+    }
+}
+
+impl Background for ConstantBackground {
+    fn radiance(&self, _direction: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+// Blends from one color straight down to another straight up
+pub struct GradientBackground {
+    bottom: Vec3,
+    top: Vec3
+}
+
+impl GradientBackground {
+    pub fn new(bottom: Vec3, top: Vec3) -> GradientBackground {
+        GradientBackground {
+            bottom,
+            top
+        }
+    }
+
+    // The white to light blue sky of Ray Tracing in One Weekend
+    pub fn sky() -> GradientBackground {
+        GradientBackground::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for GradientBackground {
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let t = 0.5 * (direction.y + 1.0);
+        self.bottom * (1.0 - t) + self.top * t
+    }
+}
+
+// Image covering the whole sphere of directions in the equirectangular (latitude-longitude) layout,
+// with the top row straight up and the center of the image looking down the negative z axis. It is
+// sampled as a light, picking pixels in proportion to how bright they are and how much of the sphere
+// they cover, so a small sun is found directly instead of by chance. Within a row directions are
+// picked evenly over the band of the sphere it covers, which makes a constant map uniform.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+    intensity: f64,
+    // Turn around the y axis in radians
+    rotation: f64,
+    distribution: Distribution2D
+}
+
+impl EnvironmentMap {
+    // The image is top row first and turned by the rotation in degrees
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>, intensity: f64, rotation: f64) -> EnvironmentMap {
+        assert_eq!(pixels.len(), width * height, "environment map has the wrong number of pixels");
+
+        // Rows near the poles are squeezed into a smaller solid angle
+        let weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let (top, bottom) = band(i / width, height);
+                (0.2126 * p.x + 0.7152 * p.y + 0.0722 * p.z) * (top - bottom)
+            })
+            .collect();
+
+        EnvironmentMap {
+            width,
+            height,
+            distribution: Distribution2D::new(width.max(1), &weights),
+            pixels,
+            intensity,
+            rotation: rotation.to_radians()
+        }
+    }
+
+    // Loads a Radiance .hdr file, or any other image as sRGB colors
+    pub fn open<P: AsRef<Path>>(path: P, intensity: f64, rotation: f64) -> image::ImageResult<EnvironmentMap> {
+        let (width, height, pixels) = load_radiance(path.as_ref())?;
+        Ok(EnvironmentMap::new(width, height, pixels, intensity, rotation))
+    }
+
+    // Position in the image of a direction, with both coordinates running from 0 to 1
+    fn uv(&self, direction: Vec3) -> [f64; 2] {
+        let phi = direction.x.atan2(-direction.z) - self.rotation;
+        let theta = direction.y.clamp(-1.0, 1.0).acos();
+        [(0.5 + phi / (2.0 * PI)).rem_euclid(1.0), theta / PI]
+    }
+
+    // Inverse of `uv`, taking the cosine of the angle from the top in place of the vertical position
+    fn direction_at(&self, u: f64, cos_theta: f64) -> Vec3 {
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        Vec3::new(sin_theta * phi.sin(), cos_theta, -sin_theta * phi.cos())
+    }
+
+    // Row of the image a vertical position falls in
+    fn row(&self, v: f64) -> usize {
+        ((v * self.height as f64) as usize).min(self.height.max(1) - 1)
+    }
+
+    // The image is stretched over the sphere by 2 pi horizontally, and each row over its band of
+    // cosines, which the density over the image is constant across
+    fn solid_angle_pdf(&self, map_pdf: f64, row: usize) -> f64 {
+        let (top, bottom) = band(row, self.height);
+        map_pdf / (2.0 * PI * self.height as f64 * (top - bottom))
+    }
+
+    // Texels wrap around horizontally and stop at the poles
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.max(0).min(self.height as i64 - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Cosines of the angles from the top at the upper and lower edges of a row
+fn band(row: usize, height: usize) -> (f64, f64) {
+    let angle = |edge: usize| (PI * edge as f64 / height as f64).cos();
+    (angle(row), angle(row + 1))
+}
+
+impl Background for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        if self.pixels.is_empty() {
+            return Vec3::new(0.0, 0.0, 0.0)
+        }
+
+        let uv = self.uv(direction);
+        let x = uv[0] * self.width as f64 - 0.5;
+        let y = uv[1] * self.height as f64 - 0.5;
+        let (dx, dy) = (x - x.floor(), y - y.floor());
+        let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+
+        let value = self.texel(x0, y0) * ((1.0 - dx) * (1.0 - dy)) +
+            self.texel(x0 + 1, y0) * (dx * (1.0 - dy)) +
+            self.texel(x0, y0 + 1) * ((1.0 - dx) * dy) +
+            self.texel(x0 + 1, y0 + 1) * (dx * dy);
+        value * self.intensity
+    }
+
+    fn is_light(&self) -> bool {
+        self.intensity > 0.0 && self.distribution.integral() > 0.0
+    }
+
+    fn sample_light(&self) -> Option<LightSample> {
+        let mut rng = rng();
+        let (uv, map_pdf) = self.distribution.sample([rng.gen::<f64>(), rng.gen::<f64>()])?;
+
+        // The position within the row is spread evenly over the cosines of the band it covers
+        let row = self.row(uv[1]);
+        let (top, bottom) = band(row, self.height);
+        let fraction = (uv[1] * self.height as f64 - row as f64).clamp(0.0, 1.0);
+        let direction = self.direction_at(uv[0], top + (bottom - top) * fraction);
+
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            emission: self.radiance(direction),
+            pdf: self.solid_angle_pdf(map_pdf, row)
+        })
+    }
+
+    fn pdf_value(&self, direction: Vec3) -> f64 {
+        let uv = self.uv(direction);
+        self.solid_angle_pdf(self.distribution.pdf(uv), self.row(uv[1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::checks::{relative_difference, sphere_directions};
+    use crate::util::seed_rng;
+
+    // Uneven dim sky with a small bright sun two texels wide
+    fn sunny(rotation: f64) -> EnvironmentMap {
+        let (width, height) = (32, 16);
+        let pixels = (0..width * height)
+            .map(|i| if i == 5 * width + 20 || i == 5 * width + 21 { Vec3::new(500.0, 450.0, 400.0) } else { Vec3::new(0.2, 0.3, 0.5) * (1.0 + (i % 7) as f64 / 7.0) })
+            .collect();
+        EnvironmentMap::new(width, height, pixels, 2.0, rotation)
+    }
+
+    fn constant() -> EnvironmentMap {
+        EnvironmentMap::new(64, 32, vec![Vec3::new(1.0, 1.0, 1.0); 64 * 32], 1.0, 0.0)
+    }
+
+    #[test]
+    fn sample_matches_pdf() {
+        seed_rng(1);
+        for &rotation in [0.0, 70.0].iter() {
+            let map = sunny(rotation);
+            let mut solid_angle = 0.0;
+            for _ in 0..10_000 {
+                let sample = map.sample_light().unwrap();
+                solid_angle += 1.0 / sample.pdf / 10_000.0;
+                let pdf = map.pdf_value(sample.direction);
+                assert!(relative_difference(sample.pdf, pdf) < 1e-6, "{:?}: {} against {}", [sample.direction.x, sample.direction.y, sample.direction.z], sample.pdf, pdf);
+
+                let radiance = map.radiance(sample.direction);
+                assert_eq!([sample.emission.x, sample.emission.y, sample.emission.z], [radiance.x, radiance.y, radiance.z]);
+                assert_eq!(sample.distance, f64::INFINITY);
+            }
+
+            // The directions are spread as the densities say, so weighting by them covers the sphere
+            assert!((solid_angle / (4.0 * PI) - 1.0).abs() < 0.02, "{}", solid_angle / (4.0 * PI));
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let directions = sphere_directions(400_000);
+        for map in [constant(), sunny(0.0), sunny(70.0)].iter() {
+            let integral = directions.iter().map(|&direction| map.pdf_value(direction)).sum::<f64>() * (4.0 * PI / directions.len() as f64);
+            assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+        }
+    }
+
+    #[test]
+    fn constant_map_is_uniform() {
+        // Right up to the poles every direction is as likely as any other
+        let map = constant();
+        for direction in sphere_directions(2000).into_iter().chain(vec![Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0)]) {
+            let pdf = map.pdf_value(direction);
+            assert!(relative_difference(pdf, 1.0 / (4.0 * PI)) < 1e-9, "{:?}: {}", [direction.x, direction.y, direction.z], pdf * 4.0 * PI);
+        }
+
+        seed_rng(2);
+        for _ in 0..1000 {
+            assert!(relative_difference(map.sample_light().unwrap().pdf, 1.0 / (4.0 * PI)) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn directions_round_trip() {
+        for &rotation in [0.0, 70.0, -200.0].iter() {
+            let map = sunny(rotation);
+            for direction in sphere_directions(1000) {
+                let uv = map.uv(direction);
+                let back = map.direction_at(uv[0], (uv[1] * PI).cos());
+                assert!((back - direction).length() < 1e-9);
+            }
+        }
+
+        // The middle of the image looks down -z and the top row straight up
+        let map = constant();
+        assert!((map.direction_at(0.5, 0.0) - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+        assert!((map.direction_at(0.3, 1.0) - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+    }
+}
@@ -0,0 +1,401 @@
+use crate::material::Material;
+use crate::objects::{Hitable, SurfaceInteraction};
+use crate::util::{Aabb, Vec3, Ray};
+
+// Number of buckets used when estimating the surface area heuristic along an axis
+const BUCKET_COUNT: usize = 16;
+
+// Primitive counts at or below this may become leaves when splitting does not pay off
+const MAX_LEAF_SIZE: usize = 4;
+
+// Deepest the tree can grow, which also bounds the traversal stack
+const MAX_DEPTH: usize = 64;
+
+// Cost of visiting a node relative to intersecting a single primitive
+const TRAVERSAL_COST: f64 = 0.125;
+
+#[derive(Copy, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    // For leaves this is the first primitive, for interior nodes it is the index of the second child
+    offset: usize,
+    // Number of primitives in a leaf, zero for interior nodes
+    count: usize,
+    axis: usize
+}
+
+struct PrimitiveInfo {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vec3
+}
+
+// Flattened bounding volume hierarchy over an indexed set of primitives
+pub(crate) struct BvhTree {
+    nodes: Vec<BvhNode>
+}
+
+impl BvhTree {
+    // Builds the tree using the binned surface area heuristic. The returned order maps each
+    // leaf slot back to the index of the primitive that should be stored there.
+    pub(crate) fn build(bounds: &[Aabb]) -> (BvhTree, Vec<usize>) {
+        let mut primitives: Vec<PrimitiveInfo> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| PrimitiveInfo {
+                index,
+                bounds: *bounds,
+                centroid: bounds.centroid()
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(2 * bounds.len());
+
+        if !primitives.is_empty() {
+            build_recursive(&mut nodes, &mut primitives, 0, 0);
+        }
+
+        let order = primitives.iter().map(|primitive| primitive.index).collect();
+        (BvhTree { nodes }, order)
+    }
+
+    pub(crate) fn bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(root) => root.bounds,
+            None => Aabb::empty()
+        }
+    }
+
+    // Visits every leaf slot whose bounds the ray passes through, nearest child first. The callback
+    // receives the slot and the current closest distance, returning the new distance on a closer hit.
+    pub(crate) fn traverse<F>(&self, r: Ray, t_min: f64, t_max: f64, mut hit_primitive: F)
+        where F: FnMut(usize, f64) -> Option<f64> {
+        if self.nodes.is_empty() {
+            return
+        }
+
+        let inv_direction = Vec3::new(1.0 / r.direction.x, 1.0 / r.direction.y, 1.0 / r.direction.z);
+        let negative = [inv_direction.x < 0.0, inv_direction.y < 0.0, inv_direction.z < 0.0];
+
+        let mut closest_so_far = t_max;
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_size = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bounds.hit_inverse(r.origin, inv_direction, t_min, closest_so_far) {
+                if node.count > 0 {
+                    for slot in node.offset..node.offset + node.count {
+                        if let Some(t) = hit_primitive(slot, closest_so_far) {
+                            closest_so_far = t;
+                        }
+                    }
+                } else {
+                    // Descend into the child nearest the ray origin and defer the other one
+                    if negative[node.axis] {
+                        stack[stack_size] = current + 1;
+                        current = node.offset;
+                    } else {
+                        stack[stack_size] = node.offset;
+                        current += 1;
+                    }
+                    stack_size += 1;
+                    continue
+                }
+            }
+
+            if stack_size == 0 {
+                break
+            }
+            stack_size -= 1;
+            current = stack[stack_size];
+        }
+    }
+}
+
+fn build_recursive(nodes: &mut Vec<BvhNode>, primitives: &mut [PrimitiveInfo], offset: usize, depth: usize) -> usize {
+    let node_index = nodes.len();
+    let count = primitives.len();
+
+    let mut bounds = Aabb::empty();
+    let mut centroid_bounds = Aabb::empty();
+    for primitive in primitives.iter() {
+        bounds = bounds.union(primitive.bounds);
+        centroid_bounds = centroid_bounds.include(primitive.centroid);
+    }
+
+    nodes.push(BvhNode {
+        bounds,
+        offset,
+        count,
+        axis: 0
+    });
+
+    let extent = centroid_bounds.diagonal();
+    let axis = centroid_bounds.longest_axis();
+
+    // All centroids coincide (or the tree is too deep) so there is nothing to gain from splitting
+    if count == 1 || extent[axis] <= 0.0 || depth + 1 >= MAX_DEPTH {
+        return node_index
+    }
+
+    let bucket_of = |centroid: Vec3| {
+        let b = ((centroid[axis] - centroid_bounds.min[axis]) / extent[axis] * BUCKET_COUNT as f64) as usize;
+        b.min(BUCKET_COUNT - 1)
+    };
+
+    let mut bucket_counts = [0usize; BUCKET_COUNT];
+    let mut bucket_bounds = [Aabb::empty(); BUCKET_COUNT];
+    for primitive in primitives.iter() {
+        let b = bucket_of(primitive.centroid);
+        bucket_counts[b] += 1;
+        bucket_bounds[b] = bucket_bounds[b].union(primitive.bounds);
+    }
+
+    // Sweep from the right to find the area and count of every right-hand partition
+    let mut right_area = [0.0; BUCKET_COUNT];
+    let mut right_count = [0usize; BUCKET_COUNT];
+    let mut accumulated = Aabb::empty();
+    let mut accumulated_count = 0;
+    for b in (1..BUCKET_COUNT).rev() {
+        accumulated = accumulated.union(bucket_bounds[b]);
+        accumulated_count += bucket_counts[b];
+        right_area[b] = accumulated.surface_area();
+        right_count[b] = accumulated_count;
+    }
+
+    // Then sweep from the left evaluating the cost of splitting after each bucket
+    let mut best_cost = f64::INFINITY;
+    let mut best_split = 0;
+    let mut accumulated = Aabb::empty();
+    let mut accumulated_count = 0;
+    for b in 0..BUCKET_COUNT - 1 {
+        accumulated = accumulated.union(bucket_bounds[b]);
+        accumulated_count += bucket_counts[b];
+
+        if accumulated_count == 0 || right_count[b + 1] == 0 {
+            continue
+        }
+
+        let cost = accumulated_count as f64 * accumulated.surface_area() + right_count[b + 1] as f64 * right_area[b + 1];
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = b;
+        }
+    }
+
+    let total_area = bounds.surface_area();
+    let split_cost = if total_area > 0.0 {
+        TRAVERSAL_COST + best_cost / total_area
+    } else {
+        f64::INFINITY
+    };
+
+    if count <= MAX_LEAF_SIZE && split_cost >= count as f64 {
+        return node_index
+    }
+
+    let mut mid = 0;
+    for i in 0..count {
+        if bucket_of(primitives[i].centroid) <= best_split {
+            primitives.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    if mid == 0 || mid == count {
+        return node_index
+    }
+
+    let (left, right) = primitives.split_at_mut(mid);
+    build_recursive(nodes, left, offset, depth + 1);
+    let second_child = build_recursive(nodes, right, offset + mid, depth + 1);
+
+    nodes[node_index] = BvhNode {
+        bounds,
+        offset: second_child,
+        count: 0,
+        axis
+    };
+
+    node_index
+}
+
+// Bounding volume hierarchy over a set of hitable objects
+pub struct Bvh {
+    objects: Vec<Box<dyn Hitable>>,
+    tree: BvhTree
+}
+
+impl Bvh {
+    pub fn new(list: Vec<Box<dyn Hitable>>) -> Bvh {
+        let bounds: Vec<Aabb> = list.iter().map(|object| object.bounding_box()).collect();
+        let (tree, order) = BvhTree::build(&bounds);
+
+        // Reorder the objects so that every leaf references a contiguous range
+        let mut slots: Vec<Option<Box<dyn Hitable>>> = list.into_iter().map(Some).collect();
+        let objects = order
+            .into_iter()
+            .map(|index| slots[index].take().unwrap())
+            .collect();
+
+        Bvh {
+            objects,
+            tree
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    // The objects in the order the tree stores them
+    pub fn objects(&self) -> &[Box<dyn Hitable>] {
+        &self.objects
+    }
+}
+
+impl Hitable for Bvh {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)> {
+        let mut res = None;
+
+        self.tree.traverse(ray, t_min, t_max, |slot, closest_so_far| {
+            let (surface_interaction, material) = self.objects[slot].hit(ray, t_min, closest_so_far)?;
+            let t = surface_interaction.t;
+            res = Some((surface_interaction, material));
+            Some(t)
+        });
+        res
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::{Sphere, Triangle};
+    use crate::texture::ConstantTexture;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))))
+    }
+
+    fn random_point<R: Rng>(rng: &mut R, scale: f64) -> Vec3 {
+        Vec3::new(rng.gen_range(-scale, scale), rng.gen_range(-scale, scale), rng.gen_range(-scale, scale))
+    }
+
+    // Levels from the root to the deepest leaf
+    fn depth(tree: &BvhTree, node: usize) -> usize {
+        let BvhNode { count, offset, .. } = tree.nodes[node];
+        if count > 0 {
+            1
+        } else {
+            1 + depth(tree, node + 1).max(depth(tree, offset))
+        }
+    }
+
+    // Distance to the nearest hit found by trying every object in turn
+    fn linear_hit(bvh: &Bvh, r: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        bvh.objects()
+            .iter()
+            .filter_map(|object| object.hit(r, t_min, t_max))
+            .map(|(hit, _)| hit.t)
+            .fold(None, |closest: Option<f64>, t| Some(closest.map_or(t, |c| c.min(t))))
+    }
+
+    // Compares the nearest hit of the tree with that of trying every object in turn
+    fn assert_matches_linear_scan<R: Rng>(bvh: &Bvh, rng: &mut R, rays: usize, scale: f64) {
+        let mut hits = 0;
+        for _ in 0..rays {
+            let r = Ray::new(random_point(rng, 1.5 * scale), random_point(rng, 1.0));
+            let (t_min, t_max) = (0.001, if rng.gen::<f64>() < 0.2 { scale } else { f64::MAX });
+
+            let linear = linear_hit(bvh, r, t_min, t_max);
+            let tree = bvh.hit(r, t_min, t_max).map(|(hit, _)| hit.t);
+            assert_eq!(tree, linear);
+            hits += tree.is_some() as usize;
+        }
+        assert!(hits > rays / 10, "only {} of {} rays hit anything", hits, rays);
+    }
+
+    #[test]
+    fn nearest_hit_matches_linear_scan() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut list: Vec<Box<dyn Hitable>> = vec![];
+
+        for _ in 0..200 {
+            list.push(Box::new(Sphere::new(random_point(&mut rng, 10.0), rng.gen_range(0.05, 1.0), material())));
+        }
+        for _ in 0..200 {
+            let v0 = random_point(&mut rng, 10.0);
+            let (v1, v2) = (v0 + random_point(&mut rng, 1.5), v0 + random_point(&mut rng, 1.5));
+            list.push(Box::new(Triangle::new(v0, v1, v2, (v1 - v0).cross(v2 - v0).unit(), material())));
+        }
+        // Nested spheres and triangles turned about a shared center all have the same centroid
+        for i in 0..12 {
+            list.push(Box::new(Sphere::new(Vec3::new(2.0, 2.0, 2.0), 0.2 + 0.1 * i as f64, material())));
+            let offset = Vec3::new(1.0, 0.0, 0.0) * (i as f64 * 0.3).cos() + Vec3::new(0.0, 1.0, 0.0) * (i as f64 * 0.3).sin();
+            let (v0, v1) = (Vec3::new(-4.0, -4.0, 0.0) + offset, Vec3::new(-4.0, -4.0, 0.0) - offset);
+            let v2 = Vec3::new(-4.0, -4.0, 0.5);
+            list.push(Box::new(Triangle::new(v0, v1, v2, (v1 - v0).cross(v2 - v0).unit(), material())));
+        }
+
+        let bvh = Bvh::new(list);
+        assert_eq!(bvh.len(), 424);
+        assert_matches_linear_scan(&bvh, &mut rng, 2000, 10.0);
+
+        // Rays through the shared centers have to pick the outermost object
+        let r = Ray::new(Vec3::new(2.0, 2.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(bvh.hit(r, 0.001, f64::MAX).map(|(hit, _)| hit.t), linear_hit(&bvh, r, 0.001, f64::MAX));
+    }
+
+    #[test]
+    fn coincident_centroids_make_one_leaf() {
+        let list: Vec<Box<dyn Hitable>> = (0..20).map(|i| Box::new(Sphere::new(Vec3::new(1.0, 2.0, 3.0), 1.0 + i as f64, material())) as Box<dyn Hitable>).collect();
+        let bvh = Bvh::new(list);
+        assert_eq!(bvh.tree.nodes.len(), 1);
+        assert_eq!(bvh.tree.nodes[0].count, 20);
+
+        let r = Ray::new(Vec3::new(1.0, 2.0, 100.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!((bvh.hit(r, 0.001, f64::MAX).unwrap().0.t - 77.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn deep_trees_stop_at_max_depth() {
+        // Every sphere is twenty times further out and larger than the one before, so each split
+        // only takes off the outermost one and the tree would nest far deeper than the stack allows
+        let size = |i: i32| 20f64.powi(i);
+        let list: Vec<Box<dyn Hitable>> = (0..100).map(|i| Box::new(Sphere::new(Vec3::new(size(i), 0.0, 0.0), 0.1 * size(i), material())) as Box<dyn Hitable>).collect();
+        let bvh = Bvh::new(list);
+        assert_eq!(depth(&bvh.tree, 0), MAX_DEPTH);
+
+        let mut rng = SmallRng::seed_from_u64(2);
+        let mut hits = 0;
+        for _ in 0..1000 {
+            // Further out the sphere test overflows
+            let i = rng.gen_range(0, 50);
+            let target = Vec3::new(size(i), 0.0, 0.0) + random_point(&mut rng, 0.05 * size(i));
+            let origin = target + random_point(&mut rng, size(i));
+            let r = Ray::new(origin, target - origin);
+
+            let linear = linear_hit(&bvh, r, 0.001, f64::MAX);
+            let tree = bvh.hit(r, 0.001, f64::MAX).map(|(hit, _)| hit.t);
+            assert_eq!(tree, linear);
+            hits += tree.is_some() as usize;
+        }
+        assert!(hits > 900, "{} hits", hits);
+    }
+}
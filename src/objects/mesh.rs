@@ -0,0 +1,304 @@
+use crate::material::Material;
+use rand::Rng;
+use crate::objects::{area_pdf, triangle, Hitable, LightSample, SurfaceInteraction};
+use crate::objects::bvh::BvhTree;
+use crate::util::{rng, Aabb, Vec3, Ray};
+use std::error::Error;
+use std::fmt;
+
+// Index buffers that do not describe whole triangles over the vertices given
+#[derive(Debug)]
+pub enum MeshError {
+    // The number of indices is not a multiple of three
+    PartialFace {
+        index_count: usize
+    },
+    IndexOutOfRange {
+        face: usize,
+        index: u32,
+        vertex_count: usize
+    }
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::PartialFace { index_count } => write!(f, "{} indices do not make whole triangles", index_count),
+            MeshError::IndexOutOfRange { face, index, vertex_count } => write!(f, "face {} references vertex {} of only {}", face, index, vertex_count)
+        }
+    }
+}
+
+impl Error for MeshError {}
+
+// Triangle mesh sharing its vertex buffers between faces. Faces are three consecutive entries
+// of `indices`, each of which indexes the position, normal and uv buffers.
+pub struct TriangleMesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f64; 2]>,
+    indices: Vec<u32>,
+    material: Material,
+    tree: BvhTree,
+    // Running total of the face areas, only kept for emissive meshes so faces can be sampled by area
+    cumulative_areas: Vec<f64>
+}
+
+impl TriangleMesh {
+    // The normal and uv buffers may be empty when the source has no such attributes, in which
+    // case the geometric normal is used. Buffers that do not match the vertex count are dropped.
+    pub fn new(positions: Vec<Vec3>, mut normals: Vec<Vec3>, mut uvs: Vec<[f64; 2]>, indices: Vec<u32>, material: Material) -> Result<TriangleMesh, MeshError> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(MeshError::PartialFace { index_count: indices.len() })
+        }
+
+        if let Some(position) = indices.iter().position(|&index| index as usize >= positions.len()) {
+            return Err(MeshError::IndexOutOfRange { face: position / 3, index: indices[position], vertex_count: positions.len() })
+        }
+
+        if normals.len() != positions.len() {
+            normals.clear();
+        }
+
+        if uvs.len() != positions.len() {
+            uvs.clear();
+        }
+
+        let bounds: Vec<Aabb> = indices
+            .chunks_exact(3)
+            .map(|face| triangle::bounds(positions[face[0] as usize], positions[face[1] as usize], positions[face[2] as usize]))
+            .collect();
+
+        let (tree, order) = BvhTree::build(&bounds);
+
+        // Store the faces in leaf order so the tree can address them directly
+        let mut ordered = Vec::with_capacity(order.len() * 3);
+        for face in order {
+            ordered.extend_from_slice(&indices[3 * face..3 * face + 3]);
+        }
+
+        let mut cumulative_areas = Vec::new();
+        if material.is_emissive() {
+            let mut total = 0.0;
+            for face in ordered.chunks_exact(3) {
+                total += triangle::area(positions[face[0] as usize], positions[face[1] as usize], positions[face[2] as usize]);
+                cumulative_areas.push(total);
+            }
+        }
+
+        Ok(TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            indices: ordered,
+            material,
+            tree,
+            cumulative_areas
+        })
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn face(&self, face: usize) -> (usize, usize, usize) {
+        (self.indices[3 * face] as usize, self.indices[3 * face + 1] as usize, self.indices[3 * face + 2] as usize)
+    }
+}
+
+impl Hitable for TriangleMesh {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)> {
+        let mut closest = None;
+
+        self.tree.traverse(r, t_min, t_max, |face, closest_so_far| {
+            let (i0, i1, i2) = self.face(face);
+            let (t, u, v) = triangle::intersect(r, self.positions[i0], self.positions[i1], self.positions[i2], t_min, closest_so_far)?;
+            closest = Some((t, u, v, face));
+            Some(t)
+        });
+
+        let (t, u, v, face) = closest?;
+        let (i0, i1, i2) = self.face(face);
+
+        let p = [self.positions[i0], self.positions[i1], self.positions[i2]];
+
+        let normal = if self.normals.is_empty() {
+            (p[1] - p[0]).cross(p[2] - p[0]).unit()
+        } else {
+            triangle::shading_normal(p, [self.normals[i0], self.normals[i1], self.normals[i2]], u, v)
+        };
+
+        let uvs = if self.uvs.is_empty() {
+            triangle::DEFAULT_UVS
+        } else {
+            [self.uvs[i0], self.uvs[i1], self.uvs[i2]]
+        };
+
+        let (dpdu, dpdv) = triangle::differentials(p, uvs, normal);
+        let uv = triangle::interpolate_uv(uvs, u, v);
+
+        Some((SurfaceInteraction::new(r, t, normal, uv, dpdu, dpdv, self), &self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounds()
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive() && self.triangle_count() > 0
+    }
+
+    // Picks a face with probability proportional to its area, then a point uniformly on it
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let total = *self.cumulative_areas.last()?;
+        let mut rng = rng();
+
+        let target = rng.gen::<f64>() * total;
+        let face = self.cumulative_areas.partition_point(|&area| area <= target).min(self.triangle_count() - 1);
+        let (i0, i1, i2) = self.face(face);
+        let (v0, v1, v2) = (self.positions[i0], self.positions[i1], self.positions[i2]);
+
+        let point = triangle::sample_point(v0, v1, v2, &mut rng);
+        let normal = (v1 - v0).cross(v2 - v0);
+        LightSample::from_area(origin, point, normal.unit(), self.material.emitted(), total)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let total = match self.cumulative_areas.last() {
+            Some(total) => *total,
+            None => return 0.0
+        };
+
+        let r = Ray::new(origin, direction);
+        let mut closest = None;
+
+        self.tree.traverse(r, 0.001, f64::INFINITY, |face, closest_so_far| {
+            let (i0, i1, i2) = self.face(face);
+            let (t, _, _) = triangle::intersect(r, self.positions[i0], self.positions[i1], self.positions[i2], 0.001, closest_so_far)?;
+            closest = Some((t, face));
+            Some(t)
+        });
+
+        match closest {
+            Some((t, face)) => {
+                let (i0, i1, i2) = self.face(face);
+                let (v0, v1, v2) = (self.positions[i0], self.positions[i1], self.positions[i2]);
+                area_pdf(origin, r.point_at_parameter(t), (v1 - v0).cross(v2 - v0).unit(), total)
+            }
+            None => 0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Emission, Lambertian};
+    use crate::objects::Triangle;
+    use crate::texture::ConstantTexture;
+    use std::sync::Arc;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))))
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} and {:?}", [a.x, a.y, a.z], [b.x, b.y, b.z]);
+    }
+
+    // Slightly bent quad split into two triangles, with a normal and uv per vertex
+    fn quad() -> (Vec<Vec3>, Vec<Vec3>, Vec<[f64; 2]>, Vec<u32>) {
+        let positions = vec![Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.2), Vec3::new(1.0, 1.0, 0.0), Vec3::new(-1.0, 1.0, -0.3)];
+        let normals = vec![Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.3, 0.0, 1.0).unit(), Vec3::new(0.0, 0.4, 1.0).unit(), Vec3::new(-0.2, 0.2, 1.0).unit()];
+        let uvs = vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        (positions, normals, uvs, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn rejects_partial_faces() {
+        let (positions, normals, uvs, _) = quad();
+        match TriangleMesh::new(positions, normals, uvs, vec![0, 1, 2, 0], material()) {
+            Err(MeshError::PartialFace { index_count }) => assert_eq!(index_count, 4),
+            _ => panic!("expected a partial face")
+        }
+    }
+
+    #[test]
+    fn rejects_indices_out_of_range() {
+        let (positions, normals, uvs, _) = quad();
+        match TriangleMesh::new(positions, normals, uvs, vec![0, 1, 2, 0, 2, 4], material()) {
+            Err(MeshError::IndexOutOfRange { face, index, vertex_count }) => assert_eq!((face, index, vertex_count), (1, 4, 4)),
+            _ => panic!("expected an index out of range")
+        }
+    }
+
+    #[test]
+    fn drops_mismatched_normals_and_uvs() {
+        let (positions, _, _, indices) = quad();
+        // Normals pointing along x would tilt the shading normal if they were used
+        let normals = vec![Vec3::new(1.0, 0.0, 0.0); 3];
+        let mesh = TriangleMesh::new(positions.clone(), normals, vec![[0.5, 0.5]; 5], indices, material()).unwrap();
+
+        let r = Ray::new(Vec3::new(0.5, -0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let (interaction, _) = mesh.hit(r, 0.001, f64::MAX).unwrap();
+        let geometric = (positions[1] - positions[0]).cross(positions[2] - positions[0]).unit();
+        assert_close(interaction.normal, geometric);
+        assert!(interaction.uv != [0.5, 0.5]);
+    }
+
+    #[test]
+    fn hits_match_standalone_triangles() {
+        let (positions, normals, uvs, indices) = quad();
+        let mesh = TriangleMesh::new(positions.clone(), normals.clone(), uvs.clone(), indices.clone(), material()).unwrap();
+        assert_eq!((mesh.triangle_count(), mesh.vertex_count()), (2, 4));
+
+        let triangles: Vec<Triangle> = indices
+            .chunks_exact(3)
+            .map(|face| {
+                let (a, b, c) = (face[0] as usize, face[1] as usize, face[2] as usize);
+                Triangle::with_vertex_normals(positions[a], positions[b], positions[c], [normals[a], normals[b], normals[c]], material())
+                    .with_uvs([uvs[a], uvs[b], uvs[c]])
+            })
+            .collect();
+
+        for i in 0..100 {
+            let (x, y) = ((i % 10) as f64 / 5.0 - 0.95, (i / 10) as f64 / 5.0 - 0.95);
+            // From both sides so front_face is checked either way
+            let z = if i % 2 == 0 { 5.0 } else { -5.0 };
+            let r = Ray::new(Vec3::new(x, y, z), Vec3::new(0.01, -0.005, -z).unit());
+
+            let expected = triangles.iter().filter_map(|triangle| triangle.hit(r, 0.001, f64::MAX)).min_by(|a, b| a.0.t.partial_cmp(&b.0.t).unwrap());
+            let (hit, expected) = match (mesh.hit(r, 0.001, f64::MAX), expected) {
+                (Some((hit, _)), Some((expected, _))) => (hit, expected),
+                (hit, expected) => panic!("ray {} hit the mesh: {}, a triangle: {}", i, hit.is_some(), expected.is_some())
+            };
+
+            assert!((hit.t - expected.t).abs() < 1e-12);
+            assert_close(hit.point, expected.point);
+            assert_close(hit.normal, expected.normal);
+            assert_close(hit.dpdu, expected.dpdu);
+            assert_close(hit.dpdv, expected.dpdv);
+            assert!((hit.uv[0] - expected.uv[0]).abs() < 1e-12 && (hit.uv[1] - expected.uv[1]).abs() < 1e-12);
+            assert_eq!(hit.front_face, expected.front_face);
+        }
+
+        let miss = Ray::new(Vec3::new(3.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(miss, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn hits_report_the_whole_mesh() {
+        // The hit names the mesh rather than the face, and its density covers the area of both faces
+        let (v0, v1, v2, v3) = (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        let light = Material::Emission(Emission::new(Vec3::new(1.0, 1.0, 1.0)));
+        let mesh = TriangleMesh::new(vec![v0, v1, v2, v3], vec![], vec![], vec![0, 1, 2, 1, 3, 2], light).unwrap();
+        let r = Ray::new(Vec3::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let (interaction, _) = mesh.hit(r, 0.001, f64::MAX).unwrap();
+        assert!(std::ptr::addr_eq(interaction.primitive, &mesh));
+        assert!((interaction.primitive.pdf_value(r.origin, r.direction) - 1.0).abs() < 1e-12);
+    }
+}
@@ -1,30 +1,146 @@
 use crate::material::Material;
-use crate::util::{Vec3, Ray};
+use crate::util::{coordinate_system, Aabb, Vec3, Ray};
 
 pub mod sphere;
 pub mod triangle;
+pub mod bvh;
+pub mod mesh;
+pub mod scene;
+pub mod background;
 
 pub use sphere::Sphere;
 pub use triangle::Triangle;
+pub use bvh::Bvh;
+pub use mesh::{MeshError, TriangleMesh};
+pub use scene::Scene;
+pub use background::*;
 
-pub struct SurfaceInteraction {
+pub struct SurfaceInteraction<'a> {
     pub t: f64,
     pub point: Vec3,
+    // Points out of the surface regardless of which side the ray arrived from
     pub normal: Vec3,
+    pub uv: [f64; 2],
+    // Partial derivatives of the point with respect to the texture coordinates
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // Whether the ray arrived from the side the normal points towards
+    pub front_face: bool,
+    // Object the ray hit as it sits in the scene, which for a mesh is the whole mesh rather than the
+    // face. Emitters are sampled as such units, so its `pdf_value` is the density of the point having
+    // been picked among all the light the object gives off.
+    pub primitive: &'a dyn Hitable
 }
 
-impl SurfaceInteraction {
-    pub fn new(t: f64, point: Vec3, normal: Vec3) -> SurfaceInteraction {
+impl<'a> SurfaceInteraction<'a> {
+    pub fn new(r: Ray, t: f64, normal: Vec3, uv: [f64; 2], dpdu: Vec3, dpdv: Vec3, primitive: &'a dyn Hitable) -> SurfaceInteraction<'a> {
         SurfaceInteraction {
             t,
-            point,
-            normal
+            point: r.point_at_parameter(t),
+            normal,
+            uv,
+            dpdu,
+            dpdv,
+            front_face: r.direction.dot(normal) < 0.0,
+            primitive
         }
     }
+
+    // Unit tangent along increasing u, made orthogonal to the normal
+    pub fn tangent(&self) -> Vec3 {
+        let tangent = self.dpdu - self.normal * self.normal.dot(self.dpdu);
+
+        if tangent.squared_length() > 0.0 {
+            tangent.unit()
+        } else {
+            coordinate_system(self.normal).0
+        }
+    }
+
+    pub fn bitangent(&self) -> Vec3 {
+        self.normal.cross(self.tangent())
+    }
+
+    // Expresses a direction in the shading frame, where the normal is the z axis and the tangent the x axis
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        let tangent = self.tangent();
+        let bitangent = self.normal.cross(tangent);
+        Vec3::new(v.dot(tangent), v.dot(bitangent), v.dot(self.normal))
+    }
+
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        let tangent = self.tangent();
+        let bitangent = self.normal.cross(tangent);
+        tangent * v.x + bitangent * v.y + self.normal * v.z
+    }
+}
+
+// Direction picked towards an emitter together with the light it sends back along it
+pub struct LightSample {
+    // Unit direction from the origin towards the light
+    pub direction: Vec3,
+    // How far along the direction the light is, which is infinite for the background
+    pub distance: f64,
+    pub emission: Vec3,
+    // Probability density of the direction from the origin, with respect to solid angle
+    pub pdf: f64
+}
+
+impl LightSample {
+    // Converts a point sampled uniformly over an area into a solid angle density as seen from the origin
+    pub fn from_area(origin: Vec3, point: Vec3, normal: Vec3, emission: Vec3, area: f64) -> Option<LightSample> {
+        let pdf = area_pdf(origin, point, normal, area);
+        let to_light = point - origin;
+        let distance = to_light.length();
+
+        if pdf > 0.0 {
+            Some(LightSample {
+                direction: to_light / distance,
+                distance,
+                emission,
+                pdf
+            })
+        } else {
+            None
+        }
+    }
+}
+
+// Solid angle density at the origin of picking the point uniformly from a surface of the given area,
+// or zero when the surface is seen edge-on
+pub fn area_pdf(origin: Vec3, point: Vec3, normal: Vec3, area: f64) -> f64 {
+    let to_light = point - origin;
+    let distance_squared = to_light.squared_length();
+    let cosine = normal.dot(to_light).abs() / distance_squared.sqrt();
+
+    if cosine < 1e-8 || area <= 0.0 {
+        0.0
+    } else {
+        distance_squared / (cosine * area)
+    }
 }
 
 pub trait Hitable: Sync {
-    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction, &Material)>;
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)>;
+
+    // Axis-aligned box enclosing everything this object can be hit on
+    fn bounding_box(&self) -> Aabb;
+
+    // Emitters that return true are sampled directly by the integrator, so light reaching
+    // them through a diffuse bounce is not counted a second time
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // Picks a point on the surface as seen from the origin
+    fn sample_light(&self, _origin: Vec3) -> Option<LightSample> {
+        None
+    }
+
+    // Density with respect to solid angle of `sample_light` picking the direction from the origin
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
 }
 
 pub struct HitableList {
@@ -40,7 +156,7 @@ impl HitableList {
 }
 
 impl Hitable for HitableList {
-    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction, &Material)> {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)> {
         let mut closest_so_far = t_max;
         let mut res = None;
 
@@ -52,4 +168,10 @@ impl Hitable for HitableList {
         }
         res
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.list
+            .iter()
+            .fold(Aabb::empty(), |bounds, object| bounds.union(object.bounding_box()))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,198 @@
+use rand::Rng;
+use crate::light::Light;
+use crate::material::Material;
+use crate::objects::{Background, Bvh, GradientBackground, Hitable, SurfaceInteraction};
+use crate::util::{power_heuristic, rng, Vec3, Ray};
+
+// Everything needed to render: the objects, the emitters among them and the lights placed on their
+// own that are all sampled directly, and the background seen by rays leaving the scene
+pub struct Scene {
+    world: Bvh,
+    // Indices of the emissive objects in the world
+    emitters: Vec<usize>,
+    lights: Vec<Box<dyn Light>>,
+    background: Box<dyn Background>
+}
+
+impl Scene {
+    // Lights the scene with the sky gradient
+    pub fn new(world: Bvh) -> Scene {
+        Scene::with_background(world, Box::new(GradientBackground::sky()))
+    }
+
+    pub fn with_background(world: Bvh, background: Box<dyn Background>) -> Scene {
+        Scene::with_lights(world, vec![], background)
+    }
+
+    pub fn with_lights(world: Bvh, lights: Vec<Box<dyn Light>>, background: Box<dyn Background>) -> Scene {
+        let emitters = world
+            .objects()
+            .iter()
+            .enumerate()
+            .filter(|(_, object)| object.is_light())
+            .map(|(index, _)| index)
+            .collect();
+
+        Scene {
+            world,
+            emitters,
+            lights,
+            background
+        }
+    }
+
+    pub fn world(&self) -> &Bvh {
+        &self.world
+    }
+
+    pub fn lights(&self) -> &[Box<dyn Light>] {
+        &self.lights
+    }
+
+    pub fn background(&self) -> &dyn Background {
+        self.background.as_ref()
+    }
+
+    // Everything sampled directly: emitters, lights and the background when it is one
+    pub fn light_count(&self) -> usize {
+        self.emitters.len() + self.lights.len() + self.background.is_light() as usize
+    }
+
+    pub fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)> {
+        self.world.hit(ray, t_min, t_max)
+    }
+
+    // Nearest of the lights with an area met by the ray, along with the radiance seen on it
+    pub fn hit_light(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(&dyn Light, Vec3)> {
+        let mut closest = None;
+        let mut closest_t = t_max;
+
+        for light in self.lights.iter() {
+            if let Some((t, radiance)) = light.hit(ray, t_min, closest_t) {
+                closest_t = t;
+                closest = Some((light.as_ref(), radiance));
+            }
+        }
+        closest
+    }
+
+    // Density with respect to solid angle of `sample_lights` picking the direction towards the emitter
+    pub fn emitter_pdf(&self, emitter: &dyn Hitable, origin: Vec3, direction: Vec3) -> f64 {
+        match self.light_count() {
+            0 => 0.0,
+            count => emitter.pdf_value(origin, direction) / count as f64
+        }
+    }
+
+    // As `emitter_pdf`, for one of the lights
+    pub fn light_pdf(&self, light: &dyn Light, origin: Vec3, direction: Vec3) -> f64 {
+        match self.light_count() {
+            0 => 0.0,
+            count => light.pdf_value(origin, direction) / count as f64
+        }
+    }
+
+    // As `emitter_pdf`, for a ray that leaves the scene and sees the background
+    pub fn background_pdf(&self, direction: Vec3) -> f64 {
+        if self.background.is_light() {
+            self.background.pdf_value(direction) / self.light_count() as f64
+        } else {
+            0.0
+        }
+    }
+
+    // Estimates the light reaching the surface straight from one randomly picked emitter, light or
+    // the background and reflected back along the incoming ray. Everything but lights at a single
+    // point or direction is weighted against the material sampling the same direction with the
+    // power heuristic.
+    pub fn sample_lights(&self, r: Ray, surface_interaction: &SurfaceInteraction, material: &Material) -> Vec3 {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+
+        let count = self.light_count();
+        if count == 0 {
+            return black
+        }
+
+        // Indices run through the emitters, then the lights, then the background
+        let origin = surface_interaction.point;
+        let index = rng().gen_range(0, count);
+        let (sample, delta) = if index < self.emitters.len() {
+            (self.world.objects()[self.emitters[index]].sample_light(origin), false)
+        } else if index < self.emitters.len() + self.lights.len() {
+            let light = &self.lights[index - self.emitters.len()];
+            (light.sample_light(origin), light.is_delta())
+        } else {
+            (self.background.sample_light(), false)
+        };
+
+        let sample = match sample {
+            Some(sample) if sample.pdf > 0.0 && sample.pdf.is_finite() => sample,
+            _ => return black
+        };
+
+        let reflected = material.eval(r, surface_interaction, sample.direction);
+        if reflected.x <= 0.0 && reflected.y <= 0.0 && reflected.z <= 0.0 {
+            return black
+        }
+
+        // The shadow ray stops just short of the light so it does not hit the sampled point itself.
+        // Lights with an area block it as well, as they absorb the rays that follow the material.
+        let shadow_ray = Ray::new(origin, sample.direction);
+        let (t_min, t_max) = (0.001, sample.distance - 0.001);
+        if self.world.hit(shadow_ray, t_min, t_max).is_some() || self.hit_light(shadow_ray, t_min, t_max).is_some() {
+            return black
+        }
+
+        let light_pdf = sample.pdf / count as f64;
+        let weight = if delta {
+            1.0
+        } else {
+            power_heuristic(light_pdf, material.pdf(r, surface_interaction, sample.direction))
+        };
+
+        reflected * sample.emission * (weight / light_pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::{PointLight, RectLight};
+    use crate::material::Lambertian;
+    use crate::objects::{ConstantBackground, Sphere};
+    use crate::texture::ConstantTexture;
+    use crate::util::seed_rng;
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    // Light reaching the top of a large grey sphere from a point light above it, with a dark panel
+    // facing the given way in between
+    fn direct_light(panel_normal: Vec3) -> Vec3 {
+        let grey = Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))));
+        let world = Bvh::new(vec![Box::new(Sphere::new(Vec3::new(0.0, -100.0, 0.0), 100.0, grey))]);
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let lights: Vec<Box<dyn Light>> = vec![
+            Box::new(PointLight::new(Vec3::new(0.0, 4.0, 0.0), Vec3::new(16.0, 16.0, 16.0))),
+            Box::new(RectLight::centered(Vec3::new(0.0, 2.0, 0.0), panel_normal, 1.0, 1.0, black))
+        ];
+        let scene = Scene::with_lights(world, lights, Box::new(ConstantBackground::new(black)));
+
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let (surface_interaction, material) = scene.hit(r, 0.001, f64::INFINITY).unwrap();
+        seed_rng(1);
+        (0..100).fold(black, |total, _| total + scene.sample_lights(r, &surface_interaction, material))
+    }
+
+    #[test]
+    fn area_lights_cast_shadows() {
+        // Facing the surface, the panel blocks the point light
+        let blocked = direct_light(Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!([blocked.x, blocked.y, blocked.z], [0.0, 0.0, 0.0]);
+
+        // Its back lets the light through on the samples that pick the point light, each of which
+        // brings the unit irradiance times the diffuse BRDF over the chance of picking it
+        let lit = direct_light(Vec3::new(0.0, 1.0, 0.0));
+        let picked = lit.x / (0.5 / PI * 2.0);
+        assert!((picked - picked.round()).abs() < 1e-9 && picked > 30.0 && picked < 70.0, "{}", picked);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::material::Material;
-use crate::objects::{Hitable, SurfaceInteraction};
-use crate::util::{Vec3, Ray};
+use rand::Rng;
+use crate::objects::{area_pdf, Hitable, LightSample, SurfaceInteraction};
+use crate::util::{coordinate_system, rng, Aabb, Vec3, Ray};
 
 pub struct Sphere {
     center: Vec3,
@@ -18,8 +19,48 @@ impl Sphere {
     }
 }
 
+impl Sphere {
+    // Spherical mapping with v running from the bottom pole to the top, and the partial
+    // derivatives of the surface with respect to it
+    fn surface_coordinates(&self, n: Vec3) -> ([f64; 2], Vec3, Vec3) {
+        let pi = std::f64::consts::PI;
+        let theta = (-n.y).clamp(-1.0, 1.0).acos();
+        let phi = (-n.z).atan2(n.x) + pi;
+        let sin_theta = theta.sin().max(0.000001);
+
+        let dpdu = Vec3::new(n.z, 0.0, -n.x) * (2.0 * pi * self.radius);
+        let dpdv = Vec3::new(-n.x * n.y / sin_theta, sin_theta, -n.y * n.z / sin_theta) * (pi * self.radius);
+
+        ([phi / (2.0 * pi), theta / pi], dpdu, dpdv)
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * std::f64::consts::PI * self.radius * self.radius
+    }
+
+    // 1 - cos(theta_max) of the cone of directions in which the sphere is visible from the origin,
+    // written to stay accurate for small and distant spheres. None when the origin is inside.
+    fn cone(&self, origin: Vec3) -> Option<f64> {
+        let distance_squared = (self.center - origin).squared_length();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared * 1.0001 {
+            return None
+        }
+
+        let sin_squared_max = radius_squared / distance_squared;
+        Some(sin_squared_max / (1.0 + (1.0 - sin_squared_max).sqrt()))
+    }
+
+    fn interaction(&self, r: Ray, t: f64) -> SurfaceInteraction<'_> {
+        let n = (r.point_at_parameter(t) - self.center) / self.radius;
+        let (uv, dpdu, dpdv) = self.surface_coordinates(n);
+        SurfaceInteraction::new(r, t, n, uv, dpdu, dpdv, self)
+    }
+}
+
 impl Hitable for Sphere {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction, &Material)> {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)> {
         let oc: Vec3 = r.origin - self.center;
         let a: f64 = r.direction.dot(r.direction);
         let b: f64 = oc.dot(r.direction);
@@ -30,16 +71,12 @@ impl Hitable for Sphere {
             let t1 = (-b - discriminant.sqrt()) / a;
 
             if t1 < t_max && t1 > t_min {
-                let p = r.point_at_parameter(t1);
-                let n = (p - self.center) / self.radius;
-                Some((SurfaceInteraction::new(t1, p, n), &self.material))
+                Some((self.interaction(r, t1), &self.material))
             } else {
                 let t2 = (-b + discriminant.sqrt()) / a;
 
                 if t2 < t_max && t2 > t_min {
-                    let p = r.point_at_parameter(t2);
-                    let n = (p - self.center) / self.radius;
-                    Some((SurfaceInteraction::new(t2, p, n), &self.material))
+                    Some((self.interaction(r, t2), &self.material))
                 } else {
                     None
                 }
@@ -48,4 +85,140 @@ impl Hitable for Sphere {
             None
         }
     }
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Samples the cone of directions in which the sphere is visible, falling back to
+    // picking a point over the whole surface when the origin is inside
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let pi = std::f64::consts::PI;
+        let mut rng = rng();
+        let emission = self.material.emitted();
+
+        let to_center = self.center - origin;
+        let distance_squared = to_center.squared_length();
+
+        let one_minus_cos_max = match self.cone(origin) {
+            Some(one_minus_cos_max) => one_minus_cos_max,
+            None => {
+                let normal = Vec3::random_in_unit_sphere(&mut rng).unit();
+                return LightSample::from_area(origin, self.center + normal * self.radius, normal, emission, self.area())
+            }
+        };
+
+        let cos_theta = 1.0 - rng.gen::<f64>() * one_minus_cos_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * pi * rng.gen::<f64>();
+
+        let w = to_center / distance_squared.sqrt();
+        let (u, v) = coordinate_system(w);
+        let direction = u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + w * cos_theta;
+
+        // Directions at the very edge of the cone can numerically miss, in which case the
+        // tangent point is used instead
+        let t = match self.hit(Ray::new(origin, direction), 0.0, f64::INFINITY) {
+            Some((surface_interaction, _)) => surface_interaction.t,
+            None => to_center.dot(direction)
+        };
+
+        Some(LightSample {
+            direction,
+            distance: t,
+            emission,
+            pdf: 1.0 / (2.0 * pi * one_minus_cos_max)
+        })
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let (surface_interaction, _) = match self.hit(Ray::new(origin, direction), 0.001, f64::INFINITY) {
+            Some(hit) => hit,
+            None => return 0.0
+        };
+
+        match self.cone(origin) {
+            Some(one_minus_cos_max) => 1.0 / (2.0 * std::f64::consts::PI * one_minus_cos_max),
+            None => area_pdf(origin, surface_interaction.point, surface_interaction.normal, self.area())
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    fn sphere() -> Sphere {
+        let material = Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))));
+        Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0, material)
+    }
+
+    // Point on the sphere at the texture coordinates, inverting the spherical mapping
+    fn point_at(sphere: &Sphere, u: f64, v: f64) -> Vec3 {
+        let (phi, theta) = (2.0 * PI * u - PI, PI * v);
+        sphere.center + Vec3::new(theta.sin() * phi.cos(), -theta.cos(), -theta.sin() * phi.sin()) * sphere.radius
+    }
+
+    #[test]
+    fn coordinates_along_the_axes() {
+        let sphere = sphere();
+        let hit = |from: Vec3| {
+            let r = Ray::new(sphere.center + from * 10.0, -from);
+            sphere.hit(r, 0.001, f64::MAX).unwrap().0
+        };
+
+        // The seam of u lies towards -x, with v running up from the bottom pole
+        let cases = [(Vec3::new(1.0, 0.0, 0.0), [0.5, 0.5]), (Vec3::new(0.0, 0.0, -1.0), [0.75, 0.5]), (Vec3::new(0.0, 0.0, 1.0), [0.25, 0.5]), (Vec3::new(0.0, 1.0, 0.0), [0.5, 1.0]), (Vec3::new(0.0, -1.0, 0.0), [0.5, 0.0])];
+        for &(from, uv) in cases.iter() {
+            let interaction = hit(from);
+            assert!((interaction.uv[0] - uv[0]).abs() < 1e-12 && (interaction.uv[1] - uv[1]).abs() < 1e-12, "{:?}: {:?}", uv, interaction.uv);
+            assert!((interaction.normal - from).length() < 1e-12);
+            assert!(interaction.front_face);
+        }
+
+        let interaction = hit(Vec3::new(1.0, 0.0, 0.0));
+        let (dpdu, dpdv) = (interaction.dpdu, interaction.dpdv);
+        assert!((dpdu - Vec3::new(0.0, 0.0, -4.0 * PI)).length() < 1e-12);
+        assert!((dpdv - Vec3::new(0.0, 2.0 * PI, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn differentials_follow_the_mapping() {
+        let sphere = sphere();
+        let step = 1e-6;
+        for direction in crate::material::checks::sphere_directions(200) {
+            // Steer clear of the poles, where u is undefined, and the seam, where it wraps
+            if direction.y.abs() > 0.99 || (direction.x < -0.9 && direction.z.abs() < 0.1) {
+                continue
+            }
+            let r = Ray::new(sphere.center + direction * 10.0, -direction);
+            let interaction = sphere.hit(r, 0.001, f64::MAX).unwrap().0;
+            let [u, v] = interaction.uv;
+
+            assert!((point_at(&sphere, u, v) - interaction.point).length() < 1e-9);
+            let dpdu = (point_at(&sphere, u + step, v) - point_at(&sphere, u - step, v)) / (2.0 * step);
+            let dpdv = (point_at(&sphere, u, v + step) - point_at(&sphere, u, v - step)) / (2.0 * step);
+            assert!((interaction.dpdu - dpdu).length() < 1e-6 * dpdu.length(), "at {:?}", [u, v]);
+            assert!((interaction.dpdv - dpdv).length() < 1e-6 * dpdv.length(), "at {:?}", [u, v]);
+        }
+    }
+
+    #[test]
+    fn inside_hits_face_away() {
+        let sphere = sphere();
+        let r = Ray::new(sphere.center, Vec3::new(0.0, 0.0, 1.0));
+        let (interaction, _) = sphere.hit(r, 0.001, f64::MAX).unwrap();
+        assert_eq!(interaction.t, 2.0);
+        assert!(!interaction.front_face);
+        let n = interaction.normal;
+        assert_eq!([n.x, n.y, n.z], [0.0, 0.0, 1.0]);
+    }
+}
@@ -1,62 +1,261 @@
 use crate::material::Material;
-use crate::objects::{Hitable, SurfaceInteraction};
-use crate::util::{Vec3, Ray};
+use rand::Rng;
+use crate::objects::{area_pdf, Hitable, LightSample, SurfaceInteraction};
+use crate::util::{coordinate_system, rng, Aabb, Vec3, Ray};
 
 pub struct Triangle {
     v0: Vec3,
     v1: Vec3,
     v2: Vec3,
-    normal: Vec3,
+    normals: [Vec3; 3],
+    uvs: [[f64; 2]; 3],
     material: Material
 }
 
+// Texture coordinates used when none are supplied
+pub(crate) const DEFAULT_UVS: [[f64; 2]; 3] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+
 impl Triangle {
+    // Flat shaded triangle with a single normal across the face
     pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, normal: Vec3, material: Material) -> Self {
         Triangle {
             v0,
             v1,
             v2,
-            normal,
+            normals: [normal, normal, normal],
+            uvs: DEFAULT_UVS,
+            material
+        }
+    }
+
+    // Smooth shaded triangle interpolating a normal per vertex
+    pub fn with_vertex_normals(v0: Vec3, v1: Vec3, v2: Vec3, normals: [Vec3; 3], material: Material) -> Self {
+        Triangle {
+            v0,
+            v1,
+            v2,
+            normals,
+            uvs: DEFAULT_UVS,
             material
         }
     }
+
+    pub fn with_uvs(self, uvs: [[f64; 2]; 3]) -> Self {
+        Triangle {
+            uvs,
+            ..self
+        }
+    }
+}
+
+// Moller-Trumbore intersection, returning the distance along the ray and the barycentric coordinates of the hit
+pub(crate) fn intersect(r: Ray, v0: Vec3, v1: Vec3, v2: Vec3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let eps = 0.0000001;
+
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let h = r.direction.cross(edge2);
+    let a = edge1.dot(h);
+
+    if a > -eps && a < eps {
+        return None
+    }
+
+    let f = 1.0 / a;
+    let s = r.origin - v0;
+    let u = f * s.dot(h);
+
+    if !(0.0..=1.0).contains(&u) {
+        return None
+    }
+
+    let q = s.cross(edge1);
+    let v = f * r.direction.dot(q);
+
+    if v < 0.0 || u + v > 1.0 {
+        return None
+    }
+
+    let t = f * edge2.dot(q);
+
+    if t > t_min && t < t_max {
+        Some((t, u, v))
+    } else {
+        None
+    }
+}
+
+// Blends per-vertex values using the barycentric coordinates returned by `intersect`
+pub(crate) fn interpolate(n0: Vec3, n1: Vec3, n2: Vec3, u: f64, v: f64) -> Vec3 {
+    n0 * (1.0 - u - v) + n1 * u + n2 * v
+}
+
+// Normal blended from the vertex normals, or the geometric normal where they cancel out, as they
+// do between vertices whose normals point opposite ways
+pub(crate) fn shading_normal(p: [Vec3; 3], normals: [Vec3; 3], u: f64, v: f64) -> Vec3 {
+    let normal = interpolate(normals[0], normals[1], normals[2], u, v);
+    let length = normal.length();
+    if length > 1e-8 && length.is_finite() {
+        normal / length
+    } else {
+        (p[1] - p[0]).cross(p[2] - p[0]).unit()
+    }
+}
+
+pub(crate) fn interpolate_uv(uvs: [[f64; 2]; 3], u: f64, v: f64) -> [f64; 2] {
+    let w = 1.0 - u - v;
+    [
+        uvs[0][0] * w + uvs[1][0] * u + uvs[2][0] * v,
+        uvs[0][1] * w + uvs[1][1] * u + uvs[2][1] * v
+    ]
+}
+
+// Partial derivatives of the surface with respect to the texture coordinates, falling back to an
+// arbitrary tangent frame around the normal when the uv mapping is degenerate
+pub(crate) fn differentials(p: [Vec3; 3], uvs: [[f64; 2]; 3], normal: Vec3) -> (Vec3, Vec3) {
+    let duv02 = [uvs[0][0] - uvs[2][0], uvs[0][1] - uvs[2][1]];
+    let duv12 = [uvs[1][0] - uvs[2][0], uvs[1][1] - uvs[2][1]];
+    let dp02 = p[0] - p[2];
+    let dp12 = p[1] - p[2];
+    let determinant = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+
+    if determinant.abs() < 1e-12 {
+        return coordinate_system(normal)
+    }
+
+    let inv_determinant = 1.0 / determinant;
+    (
+        (dp02 * duv12[1] - dp12 * duv02[1]) * inv_determinant,
+        (dp12 * duv02[0] - dp02 * duv12[0]) * inv_determinant
+    )
+}
+
+// Bounds of a triangle, padded so that axis-aligned triangles do not produce a zero-thickness box
+pub(crate) fn bounds(v0: Vec3, v1: Vec3, v2: Vec3) -> Aabb {
+    let padding = Vec3::new(0.0001, 0.0001, 0.0001);
+    Aabb::new(v0.min(v1).min(v2) - padding, v0.max(v1).max(v2) + padding)
+}
+
+pub(crate) fn area(v0: Vec3, v1: Vec3, v2: Vec3) -> f64 {
+    (v1 - v0).cross(v2 - v0).length() * 0.5
+}
+
+// Uniformly distributed point on the triangle
+pub(crate) fn sample_point<R: Rng>(v0: Vec3, v1: Vec3, v2: Vec3, rng: &mut R) -> Vec3 {
+    let s = rng.gen::<f64>().sqrt();
+    let t = rng.gen::<f64>();
+    interpolate(v0, v1, v2, s * (1.0 - t), s * t)
 }
 
 impl Hitable for Triangle {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction, &Material)> {
-        let eps = 0.0000001;
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<(SurfaceInteraction<'_>, &Material)> {
+        let (t, u, v) = intersect(r, self.v0, self.v1, self.v2, t_min, t_max)?;
+        let p = [self.v0, self.v1, self.v2];
+        let normal = shading_normal(p, self.normals, u, v);
+        let (dpdu, dpdv) = differentials(p, self.uvs, normal);
+        let uv = interpolate_uv(self.uvs, u, v);
+        Some((SurfaceInteraction::new(r, t, normal, uv, dpdu, dpdv, self), &self.material))
+    }
 
-        let edge1 = self.v1 - self.v0;
-        let edge2 = self.v2 - self.v0;
-        let h = r.direction.cross(edge2);
-        let a = edge1.dot(h);
+    fn bounding_box(&self) -> Aabb {
+        bounds(self.v0, self.v1, self.v2)
+    }
 
-        if a > -eps && a < eps {
-            return None
-        }
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_light(&self, origin: Vec3) -> Option<LightSample> {
+        let point = sample_point(self.v0, self.v1, self.v2, &mut rng());
+        let normal = (self.v1 - self.v0).cross(self.v2 - self.v0);
+        LightSample::from_area(origin, point, normal.unit(), self.material.emitted(), area(self.v0, self.v1, self.v2))
+    }
 
-        let f = 1.0 / a;
-        let s = r.origin - self.v0;
-        let u = f * s.dot(h);
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
 
-        if u < 0.0 || u > 1.0 {
-            return None
+        match intersect(r, self.v0, self.v1, self.v2, 0.001, f64::INFINITY) {
+            Some((t, _, _)) => {
+                let normal = (self.v1 - self.v0).cross(self.v2 - self.v0).unit();
+                area_pdf(origin, r.point_at_parameter(t), normal, area(self.v0, self.v1, self.v2))
+            }
+            None => 0.0
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::TriangleMesh;
+    use crate::texture::ConstantTexture;
+    use std::sync::Arc;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))))
+    }
 
-        let q = s.cross(edge1);
-        let v = f * r.direction.dot(q);
+    #[test]
+    fn opposite_vertex_normals_fall_back_to_geometric_normal() {
+        let (v0, v1, v2) = (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let (up, down) = (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        // Halfway between the first vertex and the other two the normals cancel out
+        let r = Ray::new(Vec3::new(0.25, 0.25, 1.0), down);
 
-        if v < 0.0 || u + v > 1.0 {
-            return None
+        let triangle = Triangle::with_vertex_normals(v0, v1, v2, [up, down, down], material());
+        let mesh = TriangleMesh::new(vec![v0, v1, v2], vec![up, down, down], vec![], vec![0, 1, 2], material()).unwrap();
+        let hitables: [&dyn Hitable; 2] = [&triangle, &mesh];
+
+        for hitable in hitables.iter() {
+            let (interaction, _) = hitable.hit(r, 0.001, f64::MAX).unwrap();
+            let normal = interaction.normal;
+            assert_eq!([normal.x, normal.y, normal.z.abs()], [0.0, 0.0, 1.0]);
+            assert!(interaction.dpdu.length().is_finite() && interaction.dpdv.length().is_finite());
         }
+    }
+
+    #[test]
+    fn vertex_normals_are_blended() {
+        let p = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)];
+        let normals = [Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+        let normal = shading_normal(p, normals, 0.5, 0.0);
+        let expected = Vec3::new(1.0, 0.0, 1.0).unit();
+        assert!((normal - expected).length() < 1e-12);
+    }
 
-        let t = f * edge2.dot(q);
+    #[test]
+    fn texture_coordinates_and_differentials() {
+        let (v0, v1, v2) = (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let up = Vec3::new(0.0, 0.0, 1.0);
 
-        if t > t_min && t < t_max {
-            let plane_hit = r.point_at_parameter(t);
-            Some((SurfaceInteraction::new(t, plane_hit, self.normal), &self.material))
-        } else {
-            None
+        // u doubles along the first edge, so the surface moves half as fast with it
+        let triangle = Triangle::new(v0, v1, v2, up, material()).with_uvs([[0.0, 0.0], [2.0, 0.0], [0.0, 1.0]]);
+        for &(z, front_face) in [(1.0, true), (-1.0, false)].iter() {
+            let r = Ray::new(Vec3::new(0.25, 0.5, z), Vec3::new(0.0, 0.0, -z));
+            let (interaction, _) = triangle.hit(r, 0.001, f64::MAX).unwrap();
+            assert_eq!(interaction.uv, [0.5, 0.5]);
+            assert_eq!(interaction.front_face, front_face);
+            let (dpdu, dpdv, n) = (interaction.dpdu, interaction.dpdv, interaction.normal);
+            assert_eq!([dpdu.x, dpdu.y, dpdu.z], [0.5, 0.0, 0.0]);
+            assert_eq!([dpdv.x, dpdv.y, dpdv.z], [0.0, 1.0, 0.0]);
+            assert_eq!([n.x, n.y, n.z], [0.0, 0.0, 1.0]);
         }
+
+        // Without coordinates of its own the triangle takes the default ones
+        let r = Ray::new(Vec3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let plain = Triangle::new(v0, v1, v2, up, material());
+        let (interaction, _) = plain.hit(r, 0.001, f64::MAX).unwrap();
+        assert_eq!(interaction.uv, [0.75, 0.5]);
+        let (dpdu, dpdv) = (interaction.dpdu, interaction.dpdv);
+        assert_eq!([dpdu.x, dpdu.y, dpdu.z], [1.0, 0.0, 0.0]);
+        assert_eq!([dpdv.x, dpdv.y, dpdv.z], [-1.0, 1.0, 0.0]);
+
+        // Coordinates that collapse to a line still give a frame around the normal
+        let flat = Triangle::new(v0, v1, v2, up, material()).with_uvs([[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]]);
+        let (interaction, _) = flat.hit(r, 0.001, f64::MAX).unwrap();
+        let (dpdu, dpdv) = (interaction.dpdu, interaction.dpdv);
+        assert!((dpdu.length() - 1.0).abs() < 1e-12 && (dpdv.length() - 1.0).abs() < 1e-12);
+        assert!(dpdu.dot(up).abs() < 1e-12 && dpdv.dot(up).abs() < 1e-12 && dpdu.dot(dpdv).abs() < 1e-12);
     }
-}
\ No newline at end of file
+}
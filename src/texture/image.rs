@@ -0,0 +1,222 @@
+use crate::texture::Texture;
+use crate::util::{read_pfm, Vec3};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+// How texture coordinates outside of [0, 1] are mapped back onto the image
+#[derive(Copy, Clone)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Mirror => {
+                let period = i.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+            WrapMode::Clamp => i.max(0).min(size - 1)
+        };
+        wrapped as usize
+    }
+}
+
+// Bilinearly filtered image, stored as linear radiance values
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+    wrap: WrapMode
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>, wrap: WrapMode) -> ImageTexture {
+        assert_eq!(pixels.len(), width * height, "image texture has the wrong number of pixels");
+
+        ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap
+        }
+    }
+
+    // Loads any format supported by the image crate, assuming sRGB encoded 8-bit channels
+    pub fn open<P: AsRef<Path>>(path: P, wrap: WrapMode) -> image::ImageResult<ImageTexture> {
+        ImageTexture::load(path.as_ref(), wrap, srgb_to_linear)
+    }
+
+    // Loads an image holding data rather than colors, such as a roughness map, without decoding sRGB
+    pub fn open_linear<P: AsRef<Path>>(path: P, wrap: WrapMode) -> image::ImageResult<ImageTexture> {
+        ImageTexture::load(path.as_ref(), wrap, |value| value as f64 / 255.0)
+    }
+
+    fn load(path: &Path, wrap: WrapMode, decode: fn(u8) -> f64) -> image::ImageResult<ImageTexture> {
+        let image = image::open(path)?.to_rgb();
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .pixels()
+            .map(|p| Vec3::new(decode(p[0]), decode(p[1]), decode(p[2])))
+            .collect();
+
+        Ok(ImageTexture::new(width as usize, height as usize, pixels, wrap))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let x = self.wrap.apply(x, self.width);
+        let y = self.wrap.apply(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: [f64; 2], _p: Vec3) -> Vec3 {
+        if self.pixels.is_empty() {
+            return Vec3::new(0.0, 1.0, 1.0)
+        }
+
+        // Images are stored top row first while v increases upwards, and texel centers sit at half offsets
+        let x = uv[0] * self.width as f64 - 0.5;
+        let y = (1.0 - uv[1]) * self.height as f64 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let dx = x - x0;
+        let dy = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        self.texel(x0, y0) * ((1.0 - dx) * (1.0 - dy)) +
+            self.texel(x0 + 1, y0) * (dx * (1.0 - dy)) +
+            self.texel(x0, y0 + 1) * ((1.0 - dx) * dy) +
+            self.texel(x0 + 1, y0 + 1) * (dx * dy)
+    }
+}
+
+// Reads an image as linear radiance, top row first. Radiance .hdr and PFM files keep their values
+// and any other format is taken to hold sRGB encoded colors.
+pub fn load_radiance(path: &Path) -> image::ImageResult<(usize, usize, Vec<Vec3>)> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("hdr") => {
+            let decoder = image::hdr::HDRDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .iter()
+                .map(|p| Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                .collect();
+
+            Ok((metadata.width as usize, metadata.height as usize, pixels))
+        }
+        Some("pfm") => read_pfm(&fs::read(path)?),
+        _ => {
+            let image = image::open(path)?.to_rgb();
+            let (width, height) = image.dimensions();
+            let pixels = image
+                .pixels()
+                .map(|p| Vec3::new(srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2])))
+                .collect();
+
+            Ok((width as usize, height as usize, pixels))
+        }
+    }
+}
+
+pub fn srgb_to_linear(value: u8) -> f64 {
+    let c = value as f64 / 255.0;
+
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{linear_to_srgb, test_dir};
+
+    // Two by two image whose texels are 1, 2 on the top row and 3, 4 below, in the red channel
+    fn image(wrap: WrapMode) -> ImageTexture {
+        let pixels = [1.0, 2.0, 3.0, 4.0].iter().map(|&value| Vec3::new(value, 0.0, 0.0)).collect();
+        ImageTexture::new(2, 2, pixels, wrap)
+    }
+
+    fn red(texture: &ImageTexture, u: f64, v: f64) -> f64 {
+        texture.value([u, v], Vec3::new(0.0, 0.0, 0.0)).x
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let wrapped = |wrap: WrapMode| (-5..=5).map(|i| wrap.apply(i, 3)).collect::<Vec<usize>>();
+        assert_eq!(wrapped(WrapMode::Repeat), [1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2]);
+        assert_eq!(wrapped(WrapMode::Mirror), [1, 2, 2, 1, 0, 0, 1, 2, 2, 1, 0]);
+        assert_eq!(wrapped(WrapMode::Clamp), [0, 0, 0, 0, 0, 0, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn texel_centers() {
+        // v runs upwards, so the top row is at the top of the uv square
+        for &wrap in [WrapMode::Repeat, WrapMode::Mirror, WrapMode::Clamp].iter() {
+            let texture = image(wrap);
+            assert_eq!(red(&texture, 0.25, 0.75), 1.0);
+            assert_eq!(red(&texture, 0.75, 0.75), 2.0);
+            assert_eq!(red(&texture, 0.25, 0.25), 3.0);
+            assert_eq!(red(&texture, 0.75, 0.25), 4.0);
+        }
+    }
+
+    #[test]
+    fn bilinear_filtering() {
+        let texture = image(WrapMode::Clamp);
+        assert_eq!(red(&texture, 0.5, 0.75), 1.5);
+        assert_eq!(red(&texture, 0.25, 0.5), 2.0);
+        assert_eq!(red(&texture, 0.5, 0.5), 2.5);
+        assert_eq!(red(&texture, 0.375, 0.625), 0.5625 * 1.0 + 0.1875 * 2.0 + 0.1875 * 3.0 + 0.0625 * 4.0);
+    }
+
+    #[test]
+    fn filtering_across_the_edges() {
+        // On the right edge, a texel past it, a texel before the left edge and a texel above the top
+        let cases = [(WrapMode::Repeat, [1.5, 1.0, 2.0, 3.0]), (WrapMode::Mirror, [2.0, 2.0, 1.0, 1.0]), (WrapMode::Clamp, [2.0, 2.0, 1.0, 1.0])];
+        for &(wrap, expected) in cases.iter() {
+            let texture = image(wrap);
+            let values = [red(&texture, 1.0, 0.75), red(&texture, 1.25, 0.75), red(&texture, -0.25, 0.75), red(&texture, 0.25, 1.25)];
+            assert_eq!(values, expected);
+        }
+    }
+
+    #[test]
+    fn srgb_decoding() {
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        assert!((srgb_to_linear(10) - 10.0 / 255.0 / 12.92).abs() < 1e-15);
+        assert!((srgb_to_linear(128) - 0.2158605).abs() < 1e-6);
+
+        // Encoding for display gives back every 8-bit value
+        for value in 0..=255u8 {
+            assert_eq!((linear_to_srgb(srgb_to_linear(value)) * 255.0).round() as u8, value);
+        }
+
+        // Color images are decoded and data images kept as they are
+        let path = test_dir("texture-srgb").join("grey.png");
+        image::RgbImage::from_pixel(1, 1, image::Rgb([128, 0, 255])).save(&path).unwrap();
+        let color = ImageTexture::open(&path, WrapMode::Repeat).unwrap().value([0.5, 0.5], Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!([color.x, color.y, color.z], [srgb_to_linear(128), 0.0, 1.0]);
+        let data = ImageTexture::open_linear(&path, WrapMode::Repeat).unwrap().value([0.5, 0.5], Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!([data.x, data.y, data.z], [128.0 / 255.0, 0.0, 1.0]);
+    }
+}
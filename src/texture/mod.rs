@@ -0,0 +1,128 @@
+use crate::util::Vec3;
+use std::sync::Arc;
+
+pub mod image;
+pub mod noise;
+
+pub use self::image::{load_radiance, ImageTexture, WrapMode};
+pub use self::noise::{NoiseTexture, Perlin};
+
+// A color which varies over a surface, evaluated at the texture coordinates and point of a hit
+pub trait Texture: Send + Sync {
+    fn value(&self, uv: [f64; 2], p: Vec3) -> Vec3;
+}
+
+pub struct ConstantTexture {
+    color: Vec3
+}
+
+impl ConstantTexture {
+    pub fn new(color: Vec3) -> ConstantTexture {
+        ConstantTexture {
+            color
+        }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _uv: [f64; 2], _p: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+// Alternates between two textures either in world space (a solid checker) or across the uv mapping
+pub struct CheckerTexture {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    scale: f64,
+    use_uv: bool
+}
+
+impl CheckerTexture {
+    // Solid checker with cubes of side 1 / scale
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> CheckerTexture {
+        CheckerTexture {
+            even,
+            odd,
+            scale,
+            use_uv: false
+        }
+    }
+
+    // Checker with `scale` squares along each texture axis
+    pub fn uv(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> CheckerTexture {
+        CheckerTexture {
+            even,
+            odd,
+            scale,
+            use_uv: true
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, uv: [f64; 2], p: Vec3) -> Vec3 {
+        let cell = if self.use_uv {
+            (uv[0] * self.scale).floor() + (uv[1] * self.scale).floor()
+        } else {
+            (p.x * self.scale).floor() + (p.y * self.scale).floor() + (p.z * self.scale).floor()
+        };
+
+        if cell as i64 % 2 == 0 {
+            self.even.value(uv, p)
+        } else {
+            self.odd.value(uv, p)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker(uv: bool, scale: f64) -> CheckerTexture {
+        let (even, odd): (Arc<dyn Texture>, Arc<dyn Texture>) = (Arc::new(ConstantTexture::new(Vec3::new(0.0, 0.0, 0.0))), Arc::new(ConstantTexture::new(Vec3::new(1.0, 1.0, 1.0))));
+        if uv {
+            CheckerTexture::uv(even, odd, scale)
+        } else {
+            CheckerTexture::new(even, odd, scale)
+        }
+    }
+
+    #[test]
+    fn solid_checker() {
+        // Cubes of side one half, counted from the origin in every direction
+        let texture = checker(false, 2.0);
+        let cases = [
+            ([0.1, 0.1, 0.1], 0.0),
+            ([0.6, 0.1, 0.1], 1.0),
+            ([0.5, 0.0, 0.0], 1.0),
+            ([0.49, 0.0, 0.0], 0.0),
+            ([0.6, 0.6, 0.1], 0.0),
+            ([0.6, 0.6, 0.6], 1.0),
+            ([-0.1, 0.1, 0.1], 1.0),
+            ([-0.1, -0.1, 0.1], 0.0),
+            ([-0.6, 0.1, 0.1], 0.0),
+            ([1.1, 0.0, -1.1], 1.0)
+        ];
+        for &(p, expected) in cases.iter() {
+            // The texture coordinates play no part
+            for &uv in [[0.0, 0.0], [0.3, 0.8]].iter() {
+                assert_eq!(texture.value(uv, Vec3::new(p[0], p[1], p[2])).x, expected, "{:?}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn uv_checker() {
+        // Four squares along each axis of the uv square
+        let texture = checker(true, 4.0);
+        let cases = [([0.1, 0.1], 0.0), ([0.3, 0.1], 1.0), ([0.1, 0.3], 1.0), ([0.3, 0.3], 0.0), ([0.25, 0.0], 1.0), ([0.99, 0.0], 1.0), ([1.0, 0.0], 0.0), ([0.99, 0.99], 0.0), ([-0.1, 0.1], 1.0)];
+        for &(uv, expected) in cases.iter() {
+            // Nor does the point
+            for &p in [Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.7, -3.2, 5.1)].iter() {
+                assert_eq!(texture.value(uv, p).x, expected, "{:?}", uv);
+            }
+        }
+    }
+}
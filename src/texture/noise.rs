@@ -0,0 +1,111 @@
+use crate::texture::Texture;
+use crate::util::{rng, Vec3};
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise using random unit vectors on a lattice
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>
+}
+
+impl Perlin {
+    pub fn new() -> Perlin {
+        let mut rng = rng();
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| (Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()) * 2.0 - Vec3::new(1.0, 1.0, 1.0)).unit())
+            .collect();
+
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+
+        Perlin {
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+            gradients
+        }
+    }
+
+    // Smoothly interpolated noise in roughly [-1, 1]
+    pub fn noise(&self, p: Vec3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        // Hermite smoothing removes the grid artifacts of plain trilinear interpolation
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accumulated = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & 255) as usize] ^
+                        self.perm_y[((j + dj) & 255) as usize] ^
+                        self.perm_z[((k + dk) & 255) as usize];
+                    let weight = Vec3::new(u - di as f64, v - dj as f64, w - dk as f64);
+
+                    let (ci, cj, ck) = (di as f64, dj as f64, dk as f64);
+                    accumulated += (ci * uu + (1.0 - ci) * (1.0 - uu)) *
+                        (cj * vv + (1.0 - cj) * (1.0 - vv)) *
+                        (ck * ww + (1.0 - ck) * (1.0 - ww)) *
+                        self.gradients[index].dot(weight);
+                }
+            }
+        }
+        accumulated
+    }
+
+    // Sum of noise at doubling frequencies and halving amplitudes
+    pub fn turbulence(&self, p: Vec3, depth: usize) -> f64 {
+        let mut accumulated = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accumulated += weight * self.noise(p);
+            weight *= 0.5;
+            p = p * 2.0;
+        }
+        accumulated.abs()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new()
+    }
+}
+
+// Marble-like veins produced by phase shifting a sine wave with turbulence
+pub struct NoiseTexture {
+    noise: Perlin,
+    color: Vec3,
+    scale: f64
+}
+
+impl NoiseTexture {
+    pub fn new(color: Vec3, scale: f64) -> NoiseTexture {
+        NoiseTexture {
+            noise: Perlin::new(),
+            color,
+            scale
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: [f64; 2], p: Vec3) -> Vec3 {
+        self.color * 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turbulence(p, 7)).sin())
+    }
+}
@@ -0,0 +1,195 @@
+use crate::util::{Vec3, Ray};
+
+// Axis-aligned bounding box used by the acceleration structures
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb {
+            min,
+            max
+        }
+    }
+
+    // An inverted box which acts as the identity for union
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY)
+        }
+    }
+
+    // Smallest box containing all the given points
+    pub fn from_points(points: &[Vec3]) -> Aabb {
+        points.iter().fold(Aabb::empty(), |bounds, p| bounds.include(*p))
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max)
+        }
+    }
+
+    // Grows the box to contain a point
+    pub fn include(self, p: Vec3) -> Aabb {
+        Aabb {
+            min: self.min.min(p),
+            max: self.max.max(p)
+        }
+    }
+
+    pub fn contains(self, p: Vec3) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x &&
+            p.y >= self.min.y && p.y <= self.max.y &&
+            p.z >= self.min.z && p.z <= self.max.z
+    }
+
+    pub fn diagonal(self) -> Vec3 {
+        self.max - self.min
+    }
+
+    // Index of the axis along which the box is widest
+    pub fn longest_axis(self) -> usize {
+        let d = self.diagonal();
+
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Center and radius of a sphere enclosing the box
+    pub fn bounding_sphere(self) -> (Vec3, f64) {
+        if self.is_empty() {
+            (Vec3::new(0.0, 0.0, 0.0), 0.0)
+        } else {
+            (self.centroid(), self.diagonal().length() * 0.5)
+        }
+    }
+
+    pub fn centroid(self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(self) -> f64 {
+        let d = self.diagonal();
+
+        if self.is_empty() {
+            0.0
+        } else {
+            2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+        }
+    }
+
+    pub fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        let inv_direction = Vec3::new(1.0 / r.direction.x, 1.0 / r.direction.y, 1.0 / r.direction.z);
+        self.hit_inverse(r.origin, inv_direction, t_min, t_max)
+    }
+
+    // Slab test with a precomputed reciprocal of the ray direction, so traversal does not divide per node
+    pub fn hit_inverse(&self, origin: Vec3, inv_direction: Vec3, mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_direction[axis];
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_direction[axis];
+
+            if inv_direction[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // Written so that a NaN from 0 * inf leaves the interval untouched
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+
+            if t_max < t_min {
+                return false
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    fn hits(origin: [f64; 3], direction: [f64; 3]) -> bool {
+        let r = Ray::new(Vec3::new(origin[0], origin[1], origin[2]), Vec3::new(direction[0], direction[1], direction[2]));
+        unit_box().hit(r, 0.001, f64::MAX)
+    }
+
+    #[test]
+    fn rays_parallel_to_an_axis() {
+        // Zero components give infinite reciprocals, of either sign
+        assert!(hits([0.5, 0.5, -5.0], [0.0, 0.0, 1.0]));
+        assert!(hits([0.5, 0.5, 5.0], [0.0, -0.0, -1.0]));
+        assert!(hits([5.0, -0.5, 0.5], [-1.0, 0.0, 0.0]));
+        assert!(!hits([1.5, 0.5, -5.0], [0.0, 0.0, 1.0]));
+        assert!(!hits([0.5, -1.5, -5.0], [-0.0, 0.0, 1.0]));
+        assert!(!hits([0.5, 0.5, 5.0], [0.0, 0.0, 1.0]));
+
+        // On the plane of a face, where 0 * inf gives NaN, the ray grazes the box
+        assert!(hits([1.0, 0.5, -5.0], [0.0, 0.0, 1.0]));
+        assert!(hits([-1.0, 1.0, -5.0], [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn rays_starting_inside() {
+        for direction in [[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.3, -0.2, 0.9], [-1.0, -1.0, -1.0]].iter() {
+            assert!(hits([0.0, 0.0, 0.0], *direction));
+            assert!(hits([0.9, -0.9, 0.5], *direction));
+        }
+    }
+
+    #[test]
+    fn interval_limits() {
+        let r = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(unit_box().hit(r, 0.0, 4.5));
+        assert!(!unit_box().hit(r, 0.0, 3.5));
+        assert!(!unit_box().hit(r, 6.5, 10.0));
+
+        let inv_direction = Vec3::new(f64::INFINITY, f64::INFINITY, 1.0);
+        assert!(unit_box().hit_inverse(Vec3::new(0.0, 0.0, -5.0), inv_direction, 0.0, f64::MAX));
+        assert!(!unit_box().hit_inverse(Vec3::new(2.0, 0.0, -5.0), inv_direction, 0.0, f64::MAX));
+    }
+
+    #[test]
+    fn union_area_and_centroid() {
+        let a = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0));
+        let b = Aabb::new(Vec3::new(-1.0, 1.0, 1.0), Vec3::new(0.5, 4.0, 2.0));
+        let union = a.union(b);
+        assert_eq!([union.min.x, union.min.y, union.min.z], [-1.0, 0.0, 0.0]);
+        assert_eq!([union.max.x, union.max.y, union.max.z], [1.0, 4.0, 3.0]);
+
+        let empty = Aabb::empty().union(a);
+        assert_eq!([empty.min.x, empty.max.z], [0.0, 3.0]);
+        assert!(Aabb::empty().is_empty() && !a.is_empty());
+
+        assert_eq!(a.surface_area(), 2.0 * (2.0 + 6.0 + 3.0));
+        assert_eq!(Aabb::empty().surface_area(), 0.0);
+        assert_eq!(Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)).surface_area(), 2.0);
+
+        let centroid = union.centroid();
+        assert_eq!([centroid.x, centroid.y, centroid.z], [0.0, 2.0, 1.5]);
+        assert_eq!(union.longest_axis(), 1);
+        assert!(union.contains(Vec3::new(1.0, 4.0, 0.0)) && !union.contains(Vec3::new(1.0, 4.1, 0.0)));
+
+        let points = Aabb::from_points(&[Vec3::new(1.0, -2.0, 0.0), Vec3::new(-1.0, 3.0, 0.5)]);
+        assert_eq!([points.min.x, points.min.y, points.max.y, points.max.z], [-1.0, -2.0, 3.0, 0.5]);
+    }
+}
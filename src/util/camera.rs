@@ -1,4 +1,4 @@
-use crate::util::{random_in_unit_disk, Vec3, Ray};
+use crate::util::{random_in_unit_disk, Aabb, Vec3, Ray};
 
 pub struct Camera {
     lower_left_corner: Vec3,
@@ -32,6 +32,17 @@ impl Camera {
         }
     }
 
+    // Places the camera along a viewing direction so that the whole bounding box is in frame
+    pub fn framing(bounds: Aabb, direction: Vec3, v_up: Vec3, v_fov: f64, aspect: f64, aperture: f64) -> Camera {
+        let (center, radius) = bounds.bounding_sphere();
+        let half_v_fov = v_fov * std::f64::consts::PI / 360.0;
+        let half_h_fov = (aspect * half_v_fov.tan()).atan();
+        let distance = radius / half_v_fov.min(half_h_fov).sin();
+
+        let look_from = center - direction.unit() * distance.max(0.001);
+        Camera::new(look_from, center, v_up, v_fov, aspect, aperture, (look_from - center).length())
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = random_in_unit_disk() * self.lens_radius;
         let offset = self.u * rd.x + self.v * rd.y;
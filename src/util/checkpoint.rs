@@ -0,0 +1,171 @@
+use crate::util::{Film, RenderSettings, RenderState};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"RRAYCKPT";
+const VERSION: u32 = 3;
+
+// What is needed besides the film to carry on with an interrupted render: the scene it was
+// rendering, the settings and seed, and how many passes were done. Checkpoint files hold both.
+#[derive(Clone)]
+pub struct Checkpoint {
+    // Built-in scene name or path to the scene or model file
+    pub scene: String,
+    pub settings: RenderSettings,
+    pub seed: u64,
+    pub state: RenderState
+}
+
+impl Checkpoint {
+    // Writes next to the file first and then moves it into place, so being stopped halfway through
+    // leaves the last checkpoint as it was
+    pub fn save<P: AsRef<Path>>(&self, path: P, film: &Film) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        self.write(&mut writer, film)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&partial, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<(Checkpoint, Film)> {
+        Checkpoint::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, film: &Film) -> io::Result<()> {
+        let settings = toml::to_string(&self.settings).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        write_string(writer, &self.scene)?;
+        write_string(writer, &settings)?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&(self.state.passes as u64).to_le_bytes())?;
+        writer.write_all(&(self.state.samples as u64).to_le_bytes())?;
+        film.write_state(writer)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<(Checkpoint, Film)> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a render checkpoint".to_string()))
+        }
+
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported checkpoint version {}", version)))
+        }
+
+        let scene = read_string(reader)?;
+        let settings: RenderSettings = toml::from_str(&read_string(reader)?).map_err(|e| invalid(format!("bad render settings: {}", e)))?;
+        let seed = read_u64(reader)?;
+        let state = RenderState {
+            passes: read_u64(reader)? as usize,
+            samples: read_u64(reader)? as usize
+        };
+        let film = Film::read_state(reader, settings.width, settings.height, settings.filter)?;
+
+        let checkpoint = Checkpoint {
+            scene,
+            settings,
+            seed,
+            state
+        };
+        Ok((checkpoint, film))
+    }
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    writer.write_all(&(string.len() as u32).to_le_bytes())?;
+    writer.write_all(string.as_bytes())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "checkpoint holds text that is not UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{Filter, Vec3};
+
+    #[test]
+    fn round_trip() {
+        let settings = RenderSettings {
+            width: 3,
+            height: 2,
+            filter: Filter::Tent {radius: 1.0},
+            ..RenderSettings::default()
+        };
+        let film = Film::new(3, 2, settings.filter);
+        let mut tile = film.tile(0, 0, 3, 2);
+        tile.add_sample(0.25, 0.75, Vec3::new(1.0, 0.5, 0.25));
+        tile.add_sample(1.5, 1.5, Vec3::new(0.1, 2.0, 3.0));
+        tile.add_sample(2.9, 0.1, Vec3::new(0.0, 0.0, 7.0));
+        film.merge(tile);
+
+        let checkpoint = Checkpoint {
+            scene: "simple".to_string(),
+            settings,
+            seed: 0x0123_4567_89ab_cdef,
+            state: RenderState {passes: 2, samples: 24}
+        };
+        let mut bytes = vec![];
+        checkpoint.write(&mut bytes, &film).unwrap();
+        let (read, read_film) = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(read.scene, checkpoint.scene);
+        assert_eq!(read.settings.width, 3);
+        assert_eq!(read.settings.height, 2);
+        assert_eq!(read.settings.filter, checkpoint.settings.filter);
+        assert_eq!(read.seed, checkpoint.seed);
+        assert_eq!(read.state, checkpoint.state);
+        assert_eq!(read_film.noise_levels(), film.noise_levels());
+        for y in 0..2 {
+            for x in 0..3 {
+                let (a, b) = (read_film.pixel(x, y), film.pixel(x, y));
+                assert_eq!([a.x, a.y, a.z], [b.x, b.y, b.z]);
+                assert_eq!(read_film.samples(x, y), film.samples(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let film = Film::new(1, 1, Filter::default());
+        let checkpoint = Checkpoint {
+            scene: "simple".to_string(),
+            settings: RenderSettings {width: 1, height: 1, ..RenderSettings::default()},
+            seed: 1,
+            state: RenderState::default()
+        };
+        let mut bytes = vec![];
+        checkpoint.write(&mut bytes, &film).unwrap();
+
+        let mut other_version = bytes.clone();
+        other_version[8] = 2;
+        assert!(Checkpoint::read(&mut other_version.as_slice()).is_err());
+        assert!(Checkpoint::read(&mut &bytes[1..]).is_err());
+        assert!(Checkpoint::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+}
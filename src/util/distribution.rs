@@ -0,0 +1,183 @@
+// Piecewise constant density over [0, 1) in proportion to a list of values, one per equally wide
+// bin, where negative values count as zero
+pub struct Distribution1D {
+    values: Vec<f64>,
+    // Running integral at the end of every bin, where the last entry is the integral of the whole function
+    cumulative: Vec<f64>
+}
+
+impl Distribution1D {
+    pub fn new(values: Vec<f64>) -> Distribution1D {
+        let values: Vec<f64> = values.into_iter().map(|value| value.max(0.0)).collect();
+        let width = 1.0 / values.len() as f64;
+        let mut total = 0.0;
+        let cumulative = values
+            .iter()
+            .map(|value| {
+                total += value * width;
+                total
+            })
+            .collect();
+
+        Distribution1D {
+            values,
+            cumulative
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.cumulative.last().copied().unwrap_or(0.0)
+    }
+
+    // Maps a uniform number to a position in [0, 1) along with its density and the bin it fell in,
+    // or None when every value is zero
+    pub fn sample(&self, u: f64) -> Option<(f64, f64, usize)> {
+        let integral = self.integral();
+        if integral <= 0.0 {
+            return None
+        }
+
+        let target = u * integral;
+        let bin = self.cumulative.partition_point(|&c| c <= target).min(self.values.len() - 1);
+
+        // Bins with nothing in them are never landed on, so the division is safe
+        let start = if bin == 0 { 0.0 } else { self.cumulative[bin - 1] };
+        let count = self.values.len();
+        let offset = ((target - start) / (self.cumulative[bin] - start)).clamp(0.0, 1.0);
+
+        // Rounding can carry a position at the very edge of the bin into its neighbour, where `pdf`
+        // would give another density
+        let mut x = (bin as f64 + offset) / count as f64;
+        while (x * count as f64) as usize > bin {
+            x = x.next_down();
+        }
+        while ((x * count as f64) as usize) < bin {
+            x = x.next_up();
+        }
+
+        Some((x, self.values[bin] / integral, bin))
+    }
+
+    // Density at a position in [0, 1)
+    pub fn pdf(&self, x: f64) -> f64 {
+        let integral = self.integral();
+        if integral <= 0.0 {
+            return 0.0
+        }
+
+        let bin = ((x * self.values.len() as f64) as usize).min(self.values.len() - 1);
+        self.values[bin] / integral
+    }
+}
+
+// Piecewise constant density over the unit square from a grid of values given row by row, sampled by
+// picking a row from its total and then a column within it
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D
+}
+
+impl Distribution2D {
+    pub fn new(width: usize, values: &[f64]) -> Distribution2D {
+        let rows: Vec<Distribution1D> = values.chunks_exact(width).map(|row| Distribution1D::new(row.to_vec())).collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+
+        Distribution2D {
+            rows,
+            marginal
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.marginal.integral()
+    }
+
+    // Returns a point as [column, row] coordinates in [0, 1) with its density
+    pub fn sample(&self, u: [f64; 2]) -> Option<([f64; 2], f64)> {
+        let (y, row_pdf, row) = self.marginal.sample(u[1])?;
+        let (x, column_pdf, _) = self.rows[row].sample(u[0])?;
+        Some(([x, y], row_pdf * column_pdf))
+    }
+
+    pub fn pdf(&self, point: [f64; 2]) -> f64 {
+        let integral = self.integral();
+        if integral <= 0.0 {
+            return 0.0
+        }
+
+        let row = ((point[1] * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        let row = &self.rows[row];
+        let column = ((point[0] * row.values.len() as f64) as usize).min(row.values.len() - 1);
+        row.values[column] / integral
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Evenly spread numbers in [0, 1), along with the ends that rounding makes hardest
+    fn uniforms() -> Vec<f64> {
+        let mut uniforms: Vec<f64> = (0..1000).map(|i| (i as f64 + 0.5) / 1000.0).collect();
+        uniforms.extend_from_slice(&[0.0, 1e-300, 0.5, 1.0 - f64::EPSILON / 2.0, 1.0 - f64::EPSILON]);
+        uniforms
+    }
+
+    #[test]
+    fn sample_density_matches_pdf() {
+        let distribution = Distribution1D::new(vec![0.3, 0.0, 2.0, 0.0, 0.0, 1.1, -4.0, 0.7, 0.0]);
+        let integral = distribution.integral();
+        assert!((integral - 4.1 / 9.0).abs() < 1e-12);
+
+        for u in uniforms() {
+            let (x, pdf, bin) = distribution.sample(u).unwrap();
+            assert!((0.0..1.0).contains(&x), "u = {} gave x = {}", u, x);
+            assert_eq!(bin, (x * 9.0) as usize, "u = {}", u);
+            assert!(pdf > 0.0, "u = {} landed in the empty bin {}", u, bin);
+            assert_eq!(pdf, distribution.pdf(x), "u = {}", u);
+        }
+    }
+
+    #[test]
+    fn sample_follows_values() {
+        let distribution = Distribution1D::new(vec![1.0, 0.0, 3.0]);
+        let mut counts = [0; 3];
+        for u in uniforms() {
+            counts[distribution.sample(u).unwrap().2] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        assert!((counts[2] as f64 / counts[0] as f64 - 3.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn nothing_to_sample() {
+        let distribution = Distribution1D::new(vec![0.0, -1.0]);
+        assert!(distribution.sample(0.5).is_none());
+        assert_eq!(distribution.pdf(0.5), 0.0);
+        assert!(Distribution2D::new(2, &[0.0; 4]).sample([0.5, 0.5]).is_none());
+    }
+
+    #[test]
+    fn sample_density_matches_pdf_2d() {
+        // Three columns by four rows, with an empty row and empty cells in the others
+        let values = [
+            1.0, 0.0, 2.0,
+            0.0, 0.0, 0.0,
+            0.0, 5.0, 0.0,
+            0.5, 0.5, 3.0
+        ];
+        let distribution = Distribution2D::new(3, &values);
+
+        let uniforms = uniforms();
+        for (i, &u) in uniforms.iter().enumerate() {
+            let v = uniforms[(i * 7 + 3) % uniforms.len()];
+            let ([x, y], pdf) = distribution.sample([u, v]).unwrap();
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y), "{:?} gave {:?}", [u, v], [x, y]);
+
+            let cell = (y * 4.0) as usize * 3 + (x * 3.0) as usize;
+            assert!(values[cell] > 0.0, "{:?} landed in the empty cell {}", [u, v], cell);
+            assert!((pdf - distribution.pdf([x, y])).abs() <= 1e-12 * pdf, "{:?}", [u, v]);
+            assert!((pdf - values[cell] / distribution.integral()).abs() <= 1e-12 * pdf, "{:?}", [u, v]);
+        }
+    }
+}
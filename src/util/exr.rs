@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+
+// Writes linear RGB pixels given top row first as an uncompressed scanline OpenEXR file with 32-bit
+// float channels, which every compositing package reads
+pub fn write_exr<W: Write>(writer: &mut W, width: usize, height: usize, pixels: &[[f32; 3]]) -> io::Result<()> {
+    // Channels are stored in alphabetical order
+    let channels = [("B", 2), ("G", 1), ("R", 0)];
+    let (max_x, max_y) = (width as i32 - 1, height as i32 - 1);
+
+    let mut header = vec![];
+    header.extend_from_slice(&20000630u32.to_le_bytes());
+    // Version 2 with no flags set, meaning a single part stored as scanlines
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut channel_list = vec![];
+    for (name, _) in channels.iter() {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        // Pixel type 2 is 32-bit float, followed by the linear flag, three reserved bytes and the sampling rates
+        channel_list.extend_from_slice(&2i32.to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let window: Vec<u8> = [0, 0, max_x, max_y].iter().flat_map(|v| v.to_le_bytes()).collect();
+
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    // Each scanline is its own block, found through a table of offsets from the start of the file
+    let line_size = width * channels.len() * 4;
+    let block_size = 8 + line_size;
+    let first_block = header.len() + 8 * height;
+    for y in 0..height {
+        header.extend_from_slice(&((first_block + y * block_size) as u64).to_le_bytes());
+    }
+    writer.write_all(&header)?;
+
+    let mut block = Vec::with_capacity(block_size);
+    for y in 0..height {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&(line_size as i32).to_le_bytes());
+
+        let row = &pixels[y * width..(y + 1) * width];
+        for (_, channel) in channels.iter() {
+            for pixel in row {
+                block.extend_from_slice(&pixel[*channel].to_le_bytes());
+            }
+        }
+        writer.write_all(&block)?;
+    }
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], position: usize) -> u32 {
+        u32::from_le_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]])
+    }
+
+    fn string_at(data: &[u8], position: &mut usize) -> String {
+        let end = *position + data[*position..].iter().position(|&c| c == 0).unwrap();
+        let string = String::from_utf8(data[*position..end].to_vec()).unwrap();
+        *position = end + 1;
+        string
+    }
+
+    #[test]
+    fn header_and_offsets() {
+        let (width, height) = (2, 3);
+        let pixels: Vec<[f32; 3]> = (0..6).map(|i| [i as f32, 10.0 + i as f32, 20.0 + i as f32]).collect();
+        let mut data = vec![];
+        write_exr(&mut data, width, height, &pixels).unwrap();
+
+        assert_eq!(u32_at(&data, 0), 20000630);
+        assert_eq!(u32_at(&data, 4), 2);
+
+        // Attributes are a name, a type, a size and the value, until an empty name
+        let mut position = 8;
+        let mut attributes = vec![];
+        loop {
+            let name = string_at(&data, &mut position);
+            if name.is_empty() {
+                break
+            }
+            let kind = string_at(&data, &mut position);
+            let size = u32_at(&data, position) as usize;
+            attributes.push((name, kind, data[position + 4..position + 4 + size].to_vec()));
+            position += 4 + size;
+        }
+
+        let names: Vec<&str> = attributes.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["channels", "compression", "dataWindow", "displayWindow", "lineOrder", "pixelAspectRatio", "screenWindowCenter", "screenWindowWidth"]);
+        assert_eq!(attributes[0].2.len(), 3 * 18 + 1);
+        assert_eq!(attributes[0].2[..2], *b"B\0");
+        assert_eq!(attributes[1].2, [0]);
+        let window: Vec<u8> = [0i32, 0, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(attributes[2].2, window);
+        assert_eq!(attributes[3].2, window);
+
+        // One offset per scanline, each pointing at a block holding its y, size and B, G, R rows
+        let line_size = width * 3 * 4;
+        let first_block = position + 8 * height;
+        for y in 0..height {
+            let offset = u32_at(&data, position + 8 * y) as usize;
+            assert_eq!(u32_at(&data, position + 8 * y + 4), 0);
+            assert_eq!(offset, first_block + y * (8 + line_size));
+            assert_eq!(u32_at(&data, offset), y as u32);
+            assert_eq!(u32_at(&data, offset + 4), line_size as u32);
+
+            let value = |index: usize| f32::from_bits(u32_at(&data, offset + 8 + 4 * index));
+            assert_eq!([value(0), value(1)], [pixels[y * width][2], pixels[y * width + 1][2]]);
+            assert_eq!([value(2), value(3)], [pixels[y * width][1], pixels[y * width + 1][1]]);
+            assert_eq!([value(4), value(5)], [pixels[y * width][0], pixels[y * width + 1][0]]);
+        }
+        assert_eq!(data.len(), first_block + height * (8 + line_size));
+    }
+}
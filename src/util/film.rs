@@ -0,0 +1,337 @@
+use crate::util::{Filter, HdrImage, OutputFormat, ToneMap, Vec3};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// Samples a pixel needs before the spread of their values says anything about its noise
+const MIN_NOISE_SAMPLES: u32 = 16;
+
+// Noise is measured relative to the brightness of a pixel, but no less than this, so that the odd
+// sample finding light in an almost black pixel does not count as a lot of noise
+const NOISE_FLOOR: f64 = 0.01;
+
+// Size of a pixel as written by `write_state`
+const PIXEL_BYTES: usize = 52;
+
+// Weighted sum of the samples reaching a pixel through the filter. The samples taken inside the
+// pixel itself are also counted, along with the sum and sum of squares of their luminance.
+#[derive(Copy, Clone, Default)]
+struct FilmPixel {
+    color: [f64; 3],
+    weight: f64,
+    samples: u32,
+    luminance: f64,
+    luminance_squared: f64
+}
+
+impl FilmPixel {
+    fn add(&mut self, color: Vec3, weight: f64) {
+        self.color[0] += color.x * weight;
+        self.color[1] += color.y * weight;
+        self.color[2] += color.z * weight;
+        self.weight += weight;
+    }
+
+    // Filters with negative lobes can ring below zero next to bright edges, which is cut off
+    fn value(&self) -> Vec3 {
+        if self.weight <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0)
+        }
+
+        let value = |c: f64| (c / self.weight).max(0.0);
+        Vec3::new(value(self.color[0]), value(self.color[1]), value(self.color[2]))
+    }
+
+    // Standard error of the mean luminance of the samples, relative to the mean
+    fn noise(&self) -> f64 {
+        if self.samples < MIN_NOISE_SAMPLES {
+            return f64::INFINITY
+        }
+
+        let n = self.samples as f64;
+        let mean = self.luminance / n;
+        let variance = ((self.luminance_squared - n * mean * mean) / (n - 1.0)).max(0.0);
+        (variance / n).sqrt() / mean.max(NOISE_FLOOR)
+    }
+}
+
+// Image being rendered, where every sample is spread over the nearby pixels by the reconstruction
+// filter. Threads add samples to their own tiles which are then merged in, so the film can be read
+// or written while the render is still going. Pixel coordinates start at the top left corner, and
+// a sample at (x, y) lies x pixels across and y pixels down from it.
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Filter,
+    pixels: Mutex<Vec<FilmPixel>>
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Film {
+        Film {
+            width,
+            height,
+            filter,
+            pixels: Mutex::new(vec![FilmPixel::default(); width * height])
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    // Tile to collect the samples taken inside the pixels from x0 to x1 and y0 to y1, exclusive of
+    // the end. It also covers the pixels around them that the filter reaches.
+    pub fn tile(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> FilmTile {
+        let reach = (self.filter.radius() - 0.5).max(0.0).ceil() as usize;
+        let (x0, y0) = (x0.saturating_sub(reach), y0.saturating_sub(reach));
+        let (x1, y1) = ((x1 + reach).min(self.width), (y1 + reach).min(self.height));
+
+        FilmTile {
+            x0,
+            y0,
+            width: x1.saturating_sub(x0),
+            height: y1.saturating_sub(y0),
+            filter: self.filter,
+            pixels: vec![FilmPixel::default(); x1.saturating_sub(x0) * y1.saturating_sub(y0)]
+        }
+    }
+
+    // Adds the samples of a tile to the film, which is safe to do from several threads at once
+    pub fn merge(&self, tile: FilmTile) {
+        let mut pixels = self.pixels.lock().unwrap_or_else(|e| e.into_inner());
+
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let source = tile.pixels[y * tile.width + x];
+                let target = &mut pixels[(tile.y0 + y) * self.width + tile.x0 + x];
+
+                for c in 0..3 {
+                    target.color[c] += source.color[c];
+                }
+                target.weight += source.weight;
+                target.samples += source.samples;
+                target.luminance += source.luminance;
+                target.luminance_squared += source.luminance_squared;
+            }
+        }
+    }
+
+    // Filtered radiance of a pixel
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels.lock().unwrap_or_else(|e| e.into_inner())[y * self.width + x].value()
+    }
+
+    // Samples taken inside a pixel, not counting those reaching it through the filter
+    pub fn samples(&self, x: usize, y: usize) -> u32 {
+        self.pixels.lock().unwrap_or_else(|e| e.into_inner())[y * self.width + x].samples
+    }
+
+    // Total of the samples taken in every pixel
+    pub fn sample_count(&self) -> u64 {
+        self.pixels.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|p| p.samples as u64).sum()
+    }
+
+    // Relative noise of every pixel, top row first, measured as the standard error of the mean
+    // luminance of the samples taken in it over that mean. Pixels with too few samples to tell are
+    // infinitely noisy.
+    pub fn noise_levels(&self) -> Vec<f64> {
+        self.pixels.lock().unwrap_or_else(|e| e.into_inner()).iter().map(FilmPixel::noise).collect()
+    }
+
+    // Snapshot of everything merged so far
+    pub fn image(&self) -> HdrImage {
+        let pixels = self.pixels.lock().unwrap_or_else(|e| e.into_inner());
+        let values = pixels
+            .iter()
+            .map(|p| {
+                let value = p.value();
+                [value.x as f32, value.y as f32, value.z as f32]
+            })
+            .collect();
+
+        HdrImage::from_pixels(self.width, self.height, values)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: OutputFormat, tone_map: &ToneMap) -> io::Result<()> {
+        self.image().save(path, format, tone_map)
+    }
+
+    // Writes the sums behind every pixel, top row first, so the render can be picked up again
+    pub fn write_state<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let pixels = self.pixels.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut bytes = Vec::with_capacity(pixels.len() * PIXEL_BYTES);
+        for pixel in pixels.iter() {
+            for value in pixel.color.iter().chain(&[pixel.weight, pixel.luminance, pixel.luminance_squared]) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&pixel.samples.to_le_bytes());
+        }
+        writer.write_all(&bytes)
+    }
+
+    // Film of the given size holding the pixels written by `write_state`
+    pub fn read_state<R: Read>(reader: &mut R, width: usize, height: usize, filter: Filter) -> io::Result<Film> {
+        let mut bytes = vec![0; width * height * PIXEL_BYTES];
+        reader.read_exact(&mut bytes)?;
+
+        let pixels = bytes
+            .chunks_exact(PIXEL_BYTES)
+            .map(|chunk| {
+                let value = |index: usize| {
+                    let mut bytes = [0; 8];
+                    bytes.copy_from_slice(&chunk[8 * index..8 * index + 8]);
+                    f64::from_le_bytes(bytes)
+                };
+                FilmPixel {
+                    color: [value(0), value(1), value(2)],
+                    weight: value(3),
+                    samples: u32::from_le_bytes([chunk[48], chunk[49], chunk[50], chunk[51]]),
+                    luminance: value(4),
+                    luminance_squared: value(5)
+                }
+            })
+            .collect();
+
+        Ok(Film {
+            width,
+            height,
+            filter,
+            pixels: Mutex::new(pixels)
+        })
+    }
+}
+
+// Part of the film that one thread adds samples to before it is merged back in
+pub struct FilmTile {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    pixels: Vec<FilmPixel>
+}
+
+impl FilmTile {
+    // Adds a sample at a position on the film to every pixel of the tile within the filter radius
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Vec3) {
+        // Samples which went wrong numerically would spoil every pixel they touch
+        if !(color.x.is_finite() && color.y.is_finite() && color.z.is_finite()) {
+            return
+        }
+
+        // Positions left of or above the tile wrap around to large values and are not counted
+        let (column, row) = ((x.floor() as usize).wrapping_sub(self.x0), (y.floor() as usize).wrapping_sub(self.y0));
+        if column < self.width && row < self.height {
+            let pixel = &mut self.pixels[row * self.width + column];
+            let luminance = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+            pixel.samples += 1;
+            pixel.luminance += luminance;
+            pixel.luminance_squared += luminance * luminance;
+        }
+
+        let radius = self.filter.radius();
+        let (x, y) = (x - self.x0 as f64 - 0.5, y - self.y0 as f64 - 0.5);
+        let from_x = (x - radius).ceil().max(0.0) as usize;
+        let from_y = (y - radius).ceil().max(0.0) as usize;
+        let to_x = ((x + radius).floor() + 1.0).max(0.0).min(self.width as f64) as usize;
+        let to_y = ((y + radius).floor() + 1.0).max(0.0).min(self.height as f64) as usize;
+
+        for py in from_y..to_y {
+            for px in from_x..to_x {
+                let weight = self.filter.evaluate(px as f64 - x, py as f64 - y);
+                if weight != 0.0 {
+                    self.pixels[py * self.width + px].add(color, weight);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{tiles, TileOrder};
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    const WIDTH: usize = 11;
+    const HEIGHT: usize = 7;
+
+    // A few samples in every pixel, with a wide range of brightness
+    fn samples() -> Vec<(f64, f64, Vec3)> {
+        let mut rng = SmallRng::seed_from_u64(9);
+        let mut samples = vec![];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                for _ in 0..20 {
+                    let color = Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()) * 10f64.powf(rng.gen_range(-2.0, 2.0));
+                    samples.push((x as f64 + rng.gen::<f64>(), y as f64 + rng.gen::<f64>(), color));
+                }
+            }
+        }
+        samples
+    }
+
+    // Film made from tiles of the given size merged in the order they come in
+    fn merged(filter: Filter, tile_size: usize, order: TileOrder, reverse: bool) -> Film {
+        let film = Film::new(WIDTH, HEIGHT, filter);
+        let samples = samples();
+        let mut film_tiles: Vec<FilmTile> = tiles(WIDTH, HEIGHT, tile_size, order)
+            .into_iter()
+            .map(|tile| {
+                let mut film_tile = film.tile(tile.x0, tile.y0, tile.x1, tile.y1);
+                for &(x, y, color) in samples.iter() {
+                    if (tile.x0..tile.x1).contains(&(x as usize)) && (tile.y0..tile.y1).contains(&(y as usize)) {
+                        film_tile.add_sample(x, y, color);
+                    }
+                }
+                film_tile
+            })
+            .collect();
+        if reverse {
+            film_tiles.reverse();
+        }
+        for film_tile in film_tiles {
+            film.merge(film_tile);
+        }
+        film
+    }
+
+    #[test]
+    fn merge_order_does_not_matter() {
+        for name in ["box", "tent", "mitchell", "lanczos"].iter() {
+            let filter = Filter::from_name(name).unwrap();
+
+            // Every sample added straight to a single tile covering the film
+            let direct = merged(filter, WIDTH.max(HEIGHT), TileOrder::Scanline, false);
+            let direct_noise = direct.noise_levels();
+
+            for &(tile_size, order, reverse) in [(1, TileOrder::Scanline, false), (3, TileOrder::Spiral, false), (3, TileOrder::Spiral, true), (4, TileOrder::Hilbert, true)].iter() {
+                let film = merged(filter, tile_size, order, reverse);
+                let noise = film.noise_levels();
+                assert_eq!(film.sample_count(), direct.sample_count());
+
+                // Sums taken in another order differ only by their rounding
+                for y in 0..HEIGHT {
+                    for x in 0..WIDTH {
+                        let (a, b) = (direct.pixel(x, y), film.pixel(x, y));
+                        assert!((a - b).length() <= 1e-12 * (1.0 + a.length()), "{} with tiles of {}: pixel ({}, {})", name, tile_size, x, y);
+                        assert_eq!(direct.samples(x, y), film.samples(x, y));
+                        let index = y * WIDTH + x;
+                        assert!((direct_noise[index] - noise[index]).abs() <= 1e-9 * direct_noise[index]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// Reconstruction filters deciding how much a sample contributes to the pixels around it. They are
+// separable, with the radius measured in pixels from the pixel center along each axis.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Filter {
+    // Averages the samples inside each pixel, which is what a radius of one half gives
+    Box {
+        radius: f64
+    },
+    Tent {
+        radius: f64
+    },
+    // Falls off as exp(-alpha x^2), shifted down to reach zero at the radius
+    Gaussian {
+        radius: f64,
+        alpha: f64
+    },
+    // Mitchell-Netravali cubic, where b = c = 1/3 trades blur against ringing
+    Mitchell {
+        radius: f64,
+        b: f64,
+        c: f64
+    },
+    // Sinc windowed by a wider sinc with tau lobes
+    Lanczos {
+        radius: f64,
+        tau: f64
+    }
+}
+
+pub const FILTER_NAMES: [&str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    // Filters with their usual parameters
+    pub fn from_name(name: &str) -> Option<Filter> {
+        let filter = match name.to_lowercase().as_str() {
+            "box" => Filter::default(),
+            "tent" => Filter::Tent { radius: 1.0 },
+            "gaussian" => Filter::Gaussian { radius: 1.5, alpha: 2.0 },
+            "mitchell" => Filter::Mitchell { radius: 2.0, b: 1.0 / 3.0, c: 1.0 / 3.0 },
+            "lanczos" => Filter::Lanczos { radius: 3.0, tau: 3.0 },
+            _ => return None
+        };
+        Some(filter)
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius } | Filter::Tent { radius } | Filter::Gaussian { radius, .. } | Filter::Mitchell { radius, .. } | Filter::Lanczos { radius, .. } => radius
+        }
+    }
+
+    // Weight of a sample at the offset from a pixel center, which may be negative for the
+    // Mitchell and Lanczos filters
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0
+        }
+
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, alpha } => ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0),
+            Filter::Mitchell { radius, b, c } => mitchell(2.0 * x / radius, b, c),
+            Filter::Lanczos { tau, .. } => sinc(x) * sinc(x / tau)
+        }
+    }
+}
+
+// The cubic over [0, 2]
+fn mitchell(x: f64, b: f64, c: f64) -> f64 {
+    let (x2, x3) = (x * x, x * x * x);
+
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x3 + (-18.0 + 12.0 * b + 6.0 * c) * x2 + (6.0 - 2.0 * b)
+    } else {
+        (-b - 6.0 * c) * x3 + (6.0 * b + 30.0 * c) * x2 + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)
+    };
+    value / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(filter: &str, x: f64, value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{} at {}: {} against {}", filter, x, value, expected);
+    }
+
+    #[test]
+    fn values_at_center_and_radius() {
+        for name in FILTER_NAMES.iter() {
+            let filter = Filter::from_name(name).unwrap();
+            let radius = filter.radius();
+            let center = match filter {
+                Filter::Mitchell { b, .. } => (6.0 - 2.0 * b) / 6.0,
+                Filter::Gaussian { radius, alpha } => 1.0 - (-alpha * radius * radius).exp(),
+                Filter::Tent { radius } => radius,
+                _ => 1.0
+            };
+            assert_close(name, 0.0, filter.evaluate(0.0, 0.0), center * center);
+
+            // The box keeps its full weight out to the edge, and every other filter is down to zero there
+            let edge = if *name == "box" { 1.0 } else { 0.0 };
+            assert_close(name, radius, filter.evaluate(radius, 0.0), edge * center);
+            assert_close(name, radius, filter.evaluate(0.0, -radius), edge * center);
+            assert_eq!(filter.evaluate(radius.next_up(), 0.0), 0.0, "{}", name);
+            assert_eq!(filter.evaluate(0.0, -radius.next_up()), 0.0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let tent = Filter::from_name("tent").unwrap();
+        assert_close("tent", 0.5, tent.evaluate(0.5, -0.5), 0.25);
+
+        let gaussian = Filter::Gaussian { radius: 1.5, alpha: 2.0 };
+        assert_close("gaussian", 1.0, gaussian.evaluate(1.0, 0.0), ((-2.0f64).exp() - (-4.5f64).exp()) * (1.0 - (-4.5f64).exp()));
+
+        // The two pieces of the Mitchell cubic meet halfway out, and it dips below zero soon after
+        let mitchell = Filter::from_name("mitchell").unwrap();
+        let center = 8.0 / 9.0;
+        assert_close("mitchell", 1.0, mitchell.evaluate(1.0f64.next_down(), 0.0), center / 18.0);
+        assert_close("mitchell", 1.0, mitchell.evaluate(1.0, 0.0), center / 18.0);
+        assert_close("mitchell", 1.5, mitchell.evaluate(1.5, 0.0), center * -5.0 / 144.0);
+        assert!((20..100).all(|step| mitchell.evaluate(1.0 + step as f64 / 100.0, 0.0) < 0.0));
+
+        // Lanczos crosses zero at every whole pixel, with a negative lobe between one and two
+        let lanczos = Filter::from_name("lanczos").unwrap();
+        for &x in [1.0, 2.0, 3.0].iter() {
+            assert_close("lanczos", x, lanczos.evaluate(x, 0.0), 0.0);
+        }
+        assert_close("lanczos", 1.5, lanczos.evaluate(1.5, 0.0), -1.0 / (1.5 * PI) * (2.0 / PI));
+        assert!((1..100).all(|step| lanczos.evaluate(1.0 + step as f64 / 100.0, 0.0) < 0.0));
+        assert!((1..100).all(|step| lanczos.evaluate(2.0 + step as f64 / 100.0, 0.0) > 0.0));
+
+        // Filters are symmetric about the pixel center
+        for name in FILTER_NAMES.iter() {
+            let filter = Filter::from_name(name).unwrap();
+            assert_eq!(filter.evaluate(0.3, 0.7), filter.evaluate(-0.3, -0.7), "{}", name);
+        }
+    }
+}
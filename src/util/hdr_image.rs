@@ -0,0 +1,122 @@
+use crate::util::{write_exr, write_pfm, ToneMap, Vec3};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Formats a rendered image can be written in. The first three keep the linear radiance as floats,
+// while the rest are 8-bit images written through the image crate.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum OutputFormat {
+    Exr,
+    Pfm,
+    Hdr,
+    Ldr(image::ImageFormat)
+}
+
+impl OutputFormat {
+    // Accepts both format names and file extensions
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        let format = match name.to_lowercase().as_str() {
+            "exr" => OutputFormat::Exr,
+            "pfm" => OutputFormat::Pfm,
+            "hdr" => OutputFormat::Hdr,
+            "png" => OutputFormat::Ldr(image::ImageFormat::PNG),
+            "jpg" | "jpeg" => OutputFormat::Ldr(image::ImageFormat::JPEG),
+            "bmp" => OutputFormat::Ldr(image::ImageFormat::BMP),
+            "pnm" | "ppm" => OutputFormat::Ldr(image::ImageFormat::PNM),
+            "tif" | "tiff" => OutputFormat::Ldr(image::ImageFormat::TIFF),
+            "tga" => OutputFormat::Ldr(image::ImageFormat::TGA),
+            "ico" => OutputFormat::Ldr(image::ImageFormat::ICO),
+            _ => return None
+        };
+        Some(format)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<OutputFormat> {
+        OutputFormat::from_name(&path.as_ref().extension()?.to_string_lossy())
+    }
+
+    pub fn is_hdr(self) -> bool {
+        !matches!(self, OutputFormat::Ldr(_))
+    }
+}
+
+// Linear radiance stored as 32-bit floats, top row first
+#[derive(Clone)]
+pub struct HdrImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 3]>
+}
+
+impl HdrImage {
+    pub fn new(width: usize, height: usize) -> HdrImage {
+        HdrImage::from_pixels(width, height, vec![[0.0; 3]; width * height])
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<[f32; 3]>) -> HdrImage {
+        assert_eq!(pixels.len(), width * height, "image has the wrong number of pixels");
+
+        HdrImage {
+            width,
+            height,
+            pixels
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[[f32; 3]] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        let p = self.pixels[y * self.width + x];
+        Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Vec3) {
+        self.pixels[y * self.width + x] = [color.x as f32, color.y as f32, color.z as f32];
+    }
+
+    // 8-bit sRGB after exposure and tone mapping
+    pub fn to_srgb8(&self, tone_map: &ToneMap) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|p| tone_map.to_srgb8(Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64)).to_vec())
+            .collect()
+    }
+
+    // Float formats keep the radiance untouched, leaving the tone mapping to be applied to 8-bit ones
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: OutputFormat, tone_map: &ToneMap) -> io::Result<()> {
+        let path = path.as_ref();
+        let (width, height) = (self.width, self.height);
+        let create = || File::create(path).map(BufWriter::new);
+
+        match format {
+            OutputFormat::Exr => {
+                let mut writer = create()?;
+                write_exr(&mut writer, width, height, &self.pixels)?;
+                writer.flush()
+            }
+            OutputFormat::Pfm => {
+                let mut writer = create()?;
+                write_pfm(&mut writer, width, height, &self.pixels)?;
+                writer.flush()
+            }
+            OutputFormat::Hdr => {
+                let mut writer = create()?;
+                let pixels: Vec<image::Rgb<f32>> = self.pixels.iter().map(|&p| image::Rgb(p)).collect();
+                image::hdr::HDREncoder::new(&mut writer).encode(&pixels, width, height)?;
+                writer.flush()
+            }
+            OutputFormat::Ldr(format) => image::save_buffer_with_format(path, &self.to_srgb8(tone_map), width as u32, height as u32, image::RGB(8), format)
+        }
+    }
+}
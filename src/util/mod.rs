@@ -1,19 +1,46 @@
-use crate::objects::{Hitable, Triangle};
-use crate::material::Material;
-use rand::{Rng, thread_rng};
-use std::path::Path;
+use rand::Rng;
 
 pub mod scenes;
 pub mod camera;
 pub mod vec3;
 pub mod ray;
 pub mod sample;
+pub mod aabb;
+pub mod model;
+pub mod random;
+pub mod scene_file;
+pub mod distribution;
+pub mod pfm;
+pub mod exr;
+pub mod hdr_image;
+pub mod tone_map;
+pub mod filter;
+pub mod film;
+pub mod tiles;
+pub mod render;
+pub mod progress;
+pub mod checkpoint;
 
 pub use scenes::*;
 pub use camera::*;
 pub use vec3::*;
 pub use ray::*;
 pub use sample::*;
+pub use aabb::*;
+pub use model::*;
+pub use random::*;
+pub use scene_file::*;
+pub use distribution::*;
+pub use pfm::*;
+pub use exr::*;
+pub use hdr_image::*;
+pub use tone_map::*;
+pub use filter::*;
+pub use film::*;
+pub use tiles::*;
+pub use render::*;
+pub use progress::*;
+pub use checkpoint::*;
 
 // Clamps a value between two bounds
 pub fn clamp<T: PartialOrd>(value: T, lower: T, upper: T) -> T {
@@ -27,7 +54,7 @@ pub fn clamp<T: PartialOrd>(value: T, lower: T, upper: T) -> T {
 }
 
 pub fn random_in_unit_disk() -> Vec3 {
-    let mut rng = thread_rng();
+    let mut rng = rng();
     let mut p = Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), 0.0) * 2.0 - Vec3::new(1.0, 1.0, 0.0);
 
     while p.squared_length() >= 1.0 {
@@ -36,6 +63,18 @@ pub fn random_in_unit_disk() -> Vec3 {
     p
 }
 
+// Builds two unit vectors which together with the unit vector n form an orthonormal basis
+pub fn coordinate_system(n: Vec3) -> (Vec3, Vec3) {
+    let sign = if n.z >= 0.0 { 1.0 } else { -1.0 };
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+
+    (
+        Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        Vec3::new(b, sign + n.y * n.y * a, -n.y)
+    )
+}
+
 pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
     v - n * 2.0 * v.dot(n)
 }
@@ -56,66 +95,23 @@ pub fn schlick(cosine: f64, refraction: f64) -> f64 {
     r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
 }
 
-pub fn load_model(list: &mut Vec<Box<dyn Hitable>>, model_name: &str) {
-    let mut rng = thread_rng();
-    let path = Path::new(model_name);
-
-    let obj = tobj::load_obj(path);
-    let (models, materials) = obj.unwrap();
-
-    for model in models {
-        let mesh = model.mesh;
-
-        let material = if model.name.starts_with("Cube") {
-            let choose_mat = rng.gen::<f64>();
-            if choose_mat < 0.75 {
-                Material::Lambertian {
-                    attenuation: Vec3::new(rng.gen::<f64>() * rng.gen::<f64>(), rng.gen::<f64>() * rng.gen::<f64>(), rng.gen::<f64>() * rng.gen::<f64>())
-                }
-            } else if choose_mat < 0.90 {
-                Material::Metal {
-                    attenuation: Vec3::new(0.5 * (1.0 + rng.gen::<f64>()), 0.5 * (1.0 + rng.gen::<f64>()), 0.5 * (1.0 + rng.gen::<f64>())),
-                    fuzziness: 0.5 * rng.gen::<f64>()
-                }
-            } else if choose_mat < 0.975 {
-                Material::Dielectric {
-                    refraction: 1.5
-                }
-            } else {
-                Material::Emission {
-                    color: Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()) * 10.0
-                }
-            }
-        } else {
-            match mesh.material_id {
-                Some(id) => {
-                    let material_info = &materials[id];
-                    Material::Lambertian {
-                        attenuation: Vec3::new(material_info.diffuse[0] as f64, material_info.diffuse[1] as f64, material_info.diffuse[2] as f64)
-                    }
-                },
-                None => {
-                    Material::Lambertian {
-                        attenuation: Vec3::new(0.5, 0.5, 0.5)
-                    }
-                }
-            }
-        };
-
-        for f in 0..mesh.indices.len() / 3 {
-            let position_index = 3 * mesh.indices[3 * f] as usize;
-            let position_index1 = 3 * mesh.indices[3 * f + 1] as usize;
-            let position_index2 = 3 * mesh.indices[3 * f + 2] as usize;
+// Empty directory of its own for a test to write files into
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-ray-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
 
-            let element = Triangle::new(
-                Vec3::new(mesh.positions[position_index] as f64, mesh.positions[position_index + 1] as f64, mesh.positions[position_index + 2] as f64),
-                Vec3::new(mesh.positions[position_index1] as f64, mesh.positions[position_index1 + 1] as f64, mesh.positions[position_index1 + 2] as f64),
-                Vec3::new(mesh.positions[position_index2] as f64, mesh.positions[position_index2 + 1] as f64, mesh.positions[position_index2 + 2] as f64),
-                Vec3::new(mesh.normals[position_index] as f64, mesh.normals[position_index + 1] as f64, mesh.normals[position_index + 2] as f64),
-                material
-            );
+// Weight of a sample taken with density `pdf` when another strategy could have produced it with density `other_pdf`
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let other_squared = other_pdf * other_pdf;
 
-            list.push(Box::new(element));
-        }
+    if pdf_squared == 0.0 || other_squared.is_infinite() {
+        0.0
+    } else {
+        pdf_squared / (pdf_squared + other_squared)
     }
-}
\ No newline at end of file
+}
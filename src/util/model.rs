@@ -0,0 +1,520 @@
+use crate::objects::{Hitable, MeshError, TriangleMesh};
+use crate::material::{Dielectric, Emission, Lambertian, Material, Metal, Principled};
+use crate::texture::{ConstantTexture, ImageTexture, Texture, WrapMode};
+use crate::util::Vec3;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Reasons an OBJ model and its materials could not be loaded
+#[derive(Debug)]
+pub enum ModelError {
+    // The OBJ file, or the MTL file it references, does not exist or cannot be opened
+    NotFound(PathBuf),
+    Parse(PathBuf, tobj::LoadError),
+    // A model lacks data it needs to be rendered, such as positions for the vertices its faces reference
+    MissingAttribute {
+        model: String,
+        attribute: &'static str
+    },
+    BadMaterialIndex {
+        model: String,
+        index: usize
+    },
+    // A model uses a material by a name its MTL file does not define
+    MissingMaterial {
+        model: String,
+        material: String
+    },
+    BadMesh {
+        model: String,
+        error: MeshError
+    },
+    Texture(PathBuf, image::ImageError)
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::NotFound(path) => write!(f, "could not open {}", path.display()),
+            ModelError::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            ModelError::MissingAttribute { model, attribute } => write!(f, "model '{}' is missing {}", model, attribute),
+            ModelError::BadMaterialIndex { model, index } => write!(f, "model '{}' references material {} which does not exist", model, index),
+            ModelError::MissingMaterial { model, material } => write!(f, "model '{}' uses material '{}' which is not defined", model, material),
+            ModelError::BadMesh { model, error } => write!(f, "model '{}' is broken: {}", model, error),
+            ModelError::Texture(path, e) => write!(f, "could not load texture {}: {}", path.display(), e)
+        }
+    }
+}
+
+impl Error for ModelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ModelError::Parse(_, e) => Some(e),
+            ModelError::BadMesh { error, .. } => Some(error),
+            ModelError::Texture(_, e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+// Loads every model in an OBJ file as a triangle mesh using the materials from its MTL file
+pub fn load_model(list: &mut Vec<Box<dyn Hitable>>, model_name: &str) -> Result<(), ModelError> {
+    load_model_with(list, model_name, |_| None)
+}
+
+// As `load_model`, but lets the caller replace the material of a model based on its name
+pub fn load_model_with<F>(list: &mut Vec<Box<dyn Hitable>>, model_name: &str, mut override_material: F) -> Result<(), ModelError>
+    where F: FnMut(&str) -> Option<Material> {
+    let path = Path::new(model_name);
+
+    // Remember where the MTL file was looked for since its texture maps are relative to it, and
+    // so that failing to open it can be told apart from failing to open the OBJ file
+    let mtl_path = RefCell::new(None);
+    let source = fs::read_to_string(path).map_err(|_| ModelError::NotFound(path.to_path_buf()))?;
+    check_face_references(&source)?;
+    let material_names = material_names(&source);
+
+    let obj = tobj::load_obj_buf(&mut source.as_bytes(), |mtl_name| {
+        let full_path = match path.parent() {
+            Some(parent) => parent.join(mtl_name),
+            None => mtl_name.to_owned()
+        };
+        mtl_path.replace(Some(full_path.clone()));
+        tobj::load_mtl(&full_path)
+    });
+    let mtl_path = mtl_path.into_inner();
+
+    let (models, materials) = obj.map_err(|e| match (e, &mtl_path) {
+        (tobj::LoadError::OpenFileFailed, Some(mtl_path)) => ModelError::NotFound(mtl_path.clone()),
+        (e @ tobj::LoadError::MaterialParseError, Some(mtl_path)) => ModelError::Parse(mtl_path.clone(), e),
+        (e, _) => ModelError::Parse(path.to_path_buf(), e)
+    })?;
+
+    let texture_dir = mtl_path
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+
+    // tobj leaves models using a material it does not know of without one, rather than failing
+    if let Some((model, material)) = material_names.into_iter().find(|(_, name)| !materials.iter().any(|m| &m.name == name)) {
+        if override_material(&model).is_none() {
+            return Err(ModelError::MissingMaterial { model, material })
+        }
+    }
+
+    let mut textures = HashMap::new();
+    let materials = materials
+        .iter()
+        .map(|material_info| mtl_material(material_info, &texture_dir, &mut textures))
+        .collect::<Result<Vec<Material>, ModelError>>()?;
+
+    for model in models {
+        let mesh = model.mesh;
+
+        if mesh.positions.is_empty() {
+            return Err(ModelError::MissingAttribute { model: model.name, attribute: "vertex positions" })
+        }
+
+        let material = match override_material(&model.name) {
+            Some(material) => material,
+            None => match mesh.material_id {
+                Some(id) => match materials.get(id) {
+                    Some(material) => material.clone(),
+                    None => return Err(ModelError::BadMaterialIndex { model: model.name, index: id })
+                },
+                None => Material::Lambertian(Lambertian::new(Arc::new(ConstantTexture::new(Vec3::new(0.5, 0.5, 0.5)))))
+            }
+        };
+
+        let positions = mesh.positions
+            .chunks_exact(3)
+            .map(|p| Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+
+        let normals = mesh.normals
+            .chunks_exact(3)
+            .map(|n| Vec3::new(n[0] as f64, n[1] as f64, n[2] as f64))
+            .collect();
+
+        let uvs = mesh.texcoords
+            .chunks_exact(2)
+            .map(|uv| [uv[0] as f64, uv[1] as f64])
+            .collect();
+
+        match TriangleMesh::new(positions, normals, uvs, mesh.indices, material) {
+            Ok(triangle_mesh) => list.push(Box::new(triangle_mesh)),
+            Err(error) => return Err(ModelError::BadMesh { model: model.name, error })
+        }
+    }
+    Ok(())
+}
+
+// tobj panics when a face refers to a vertex attribute that was never declared, so those
+// references are checked before the file is handed to it
+fn check_face_references(source: &str) -> Result<(), ModelError> {
+    let attributes = ["positions", "texture coordinates", "normals"];
+    let mut counts = [0i64; 3];
+    let mut model = String::from("unnamed_object");
+
+    for line in source.lines() {
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("v") => counts[0] += 1,
+            Some("vt") => counts[1] += 1,
+            Some("vn") => counts[2] += 1,
+            Some("o") | Some("g") => model = words.collect::<Vec<&str>>().join(" "),
+            Some("f") => {
+                for vertex in words {
+                    for (attribute, index) in vertex.split('/').enumerate().take(3) {
+                        // Malformed indices are left for tobj to report as parse errors
+                        let index: i64 = match index.parse() {
+                            Ok(index) => index,
+                            Err(_) => continue
+                        };
+
+                        let resolved = if index < 0 { counts[attribute] + index } else { index - 1 };
+                        if resolved < 0 || resolved >= counts[attribute] {
+                            return Err(ModelError::MissingAttribute { model, attribute: attributes[attribute] })
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Names given to `usemtl` by each model, in the order they appear
+fn material_names(source: &str) -> Vec<(String, String)> {
+    let mut names = vec![];
+    let mut model = String::from("unnamed_object");
+
+    for line in source.lines() {
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("o") | Some("g") => model = words.collect::<Vec<&str>>().join(" "),
+            Some("usemtl") => names.push((model.clone(), words.collect::<Vec<&str>>().join(" "))),
+            _ => {}
+        }
+    }
+    names
+}
+
+// Translates an MTL material following the illumination models of the format. Emission takes
+// priority, then materials using the PBR extension become principled, and illum 3, 5 and 8 become
+// metal. Materials that let light through with d below one or a map_d, or that have a highlight from
+// Ks, map_Ks or map_Ns under an illum other than 0 and 1, become principled as well. The remaining
+// glass models 4, 6, 7 and 9 become glass and anything else is treated as diffuse. Metal roughness
+// follows the Blender exporter, where Ns = 1000 * (1 - roughness)^2.
+//
+// Some of the format has nothing to map onto and is ignored: bump, normal and displacement maps
+// (map_Bump, bump, norm, disp), as materials do not perturb the normal, emission maps (map_Ke), as
+// emission is a single color, and the ambient Ka and map_Ka, which a path tracer has no use for.
+fn mtl_material(material_info: &tobj::Material, texture_dir: &Path, textures: &mut HashMap<(PathBuf, bool), Arc<dyn Texture>>) -> Result<Material, ModelError> {
+    let emission = mtl_color(&material_info.unknown_param, "Ke");
+    if emission.x > 0.0 || emission.y > 0.0 || emission.z > 0.0 {
+        return Ok(Material::Emission(Emission::new(emission)))
+    }
+
+    let diffuse = Vec3::new(material_info.diffuse[0] as f64, material_info.diffuse[1] as f64, material_info.diffuse[2] as f64);
+    let mut albedo = || -> Result<Arc<dyn Texture>, ModelError> {
+        if material_info.diffuse_texture.is_empty() {
+            Ok(Arc::new(ConstantTexture::new(diffuse)))
+        } else {
+            mtl_texture(&texture_dir.join(&material_info.diffuse_texture), false, textures)
+        }
+    };
+
+    let params = &material_info.unknown_param;
+    if PBR_KEYS.iter().any(|key| params.contains_key(*key)) {
+        return mtl_principled(material_info, albedo()?, texture_dir, textures)
+    }
+
+    let translucent = material_info.dissolve < 1.0 || !material_info.dissolve_texture.is_empty();
+    let highlight = material_info.specular.iter().any(|&c| c > 0.0) || !material_info.specular_texture.is_empty() || !material_info.normal_texture.is_empty();
+
+    let material = match material_info.illumination_model {
+        Some(3) | Some(5) | Some(8) => {
+            let fuzziness = shininess_roughness(material_info.shininess as f64);
+            Material::Metal(Metal::new(albedo()?, fuzziness))
+        }
+        _ if translucent => mtl_principled(material_info, albedo()?, texture_dir, textures)?,
+        Some(0) | Some(1) => Material::Lambertian(Lambertian::new(albedo()?)),
+        Some(4) | Some(6) | Some(7) | Some(9) => Material::Dielectric(Dielectric::new(mtl_refraction(material_info))),
+        _ if highlight => mtl_principled(material_info, albedo()?, texture_dir, textures)?,
+        _ => Material::Lambertian(Lambertian::new(albedo()?))
+    };
+    Ok(material)
+}
+
+// Parameters of the PBR extension to MTL, any of which makes a material principled
+const PBR_KEYS: [&str; 10] = ["Pr", "Pm", "Ps", "Pc", "Pcr", "map_Pr", "map_Pm", "map_Ps", "map_Pc", "map_Pcr"];
+
+// Maps the PBR extension onto the principled material: Pr is roughness, Pm metallic, Ps sheen, Pc
+// clearcoat and Pcr clearcoat roughness, each of which may instead come from a map_ texture. Without
+// Pr the roughness comes from map_Ns, which the Blender exporter writes its roughness map to, or Ns.
+// Ks or map_Ks sets the specular level and Ni the refractive index. A glass illumination model lets
+// all light through, while d below one lets through what it leaves, with map_d taking its place when
+// given. Subsurface has no MTL counterpart.
+fn mtl_principled(material_info: &tobj::Material, base_color: Arc<dyn Texture>, texture_dir: &Path, textures: &mut HashMap<(PathBuf, bool), Arc<dyn Texture>>) -> Result<Material, ModelError> {
+    let params = &material_info.unknown_param;
+    let constant = |value: f64| -> Arc<dyn Texture> { Arc::new(ConstantTexture::new(Vec3::new(value, value, value))) };
+    let mut principled = Principled::new(base_color);
+
+    let mut scalar = |key: &str, parameter: &mut Arc<dyn Texture>| -> Result<bool, ModelError> {
+        if let Some(map) = params.get(&format!("map_{}", key)) {
+            *parameter = mtl_texture(&texture_dir.join(map.trim()), true, textures)?;
+        } else if params.contains_key(key) {
+            *parameter = constant(mtl_color(params, key).x);
+        } else {
+            return Ok(false)
+        }
+        Ok(true)
+    };
+
+    let roughness_given = scalar("Pr", &mut principled.roughness)?;
+    scalar("Pm", &mut principled.metallic)?;
+    scalar("Ps", &mut principled.sheen)?;
+    scalar("Pc", &mut principled.clearcoat)?;
+    scalar("Pcr", &mut principled.clearcoat_roughness)?;
+
+    if !roughness_given {
+        if !material_info.normal_texture.is_empty() {
+            principled.roughness = mtl_texture(&texture_dir.join(material_info.normal_texture.trim()), true, textures)?;
+        } else if material_info.shininess > 0.0 {
+            principled.roughness = constant(shininess_roughness(material_info.shininess as f64));
+        }
+    }
+
+    // tobj reports a missing Ks as black, which would remove the highlight altogether
+    let specular = material_info.specular.iter().map(|&c| c as f64).sum::<f64>() / 3.0;
+    if !material_info.specular_texture.is_empty() {
+        principled.specular = mtl_texture(&texture_dir.join(material_info.specular_texture.trim()), true, textures)?;
+    } else if specular > 0.0 {
+        principled.specular = constant(specular);
+    }
+
+    principled.refraction = mtl_refraction(material_info);
+
+    let glass = matches!(material_info.illumination_model, Some(4) | Some(6) | Some(7) | Some(9));
+    if !material_info.dissolve_texture.is_empty() {
+        let opacity = mtl_texture(&texture_dir.join(material_info.dissolve_texture.trim()), true, textures)?;
+        principled.transmission = Arc::new(InvertedTexture { texture: opacity });
+    } else if material_info.dissolve < 1.0 {
+        principled.transmission = constant(1.0 - (material_info.dissolve as f64).max(0.0));
+    } else if glass {
+        principled.transmission = constant(1.0);
+    }
+
+    Ok(Material::Principled(principled))
+}
+
+// Ni, or the index of ordinary glass when it is not given. tobj reports a missing Ni as 1, which
+// would be no refraction at all, so that is taken as missing too.
+fn mtl_refraction(material_info: &tobj::Material) -> f64 {
+    if material_info.optical_density > 1.0 {
+        material_info.optical_density as f64
+    } else {
+        1.5
+    }
+}
+
+// Inverts the Blender exporter's Ns = 1000 * (1 - roughness)^2
+fn shininess_roughness(shininess: f64) -> f64 {
+    (1.0 - (shininess / 1000.0).max(0.0).sqrt()).clamp(0.0, 1.0)
+}
+
+// One minus a map, which turns the opacity stored in map_d into transmission
+struct InvertedTexture {
+    texture: Arc<dyn Texture>
+}
+
+impl Texture for InvertedTexture {
+    fn value(&self, uv: [f64; 2], p: Vec3) -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0) - self.texture.value(uv, p)
+    }
+}
+
+// Reads a color stored among the parameters tobj does not interpret itself
+fn mtl_color(params: &HashMap<String, String>, key: &str) -> Vec3 {
+    let values: Vec<f64> = match params.get(key) {
+        Some(value) => value.split_whitespace().filter_map(|v| v.parse().ok()).collect(),
+        None => vec![]
+    };
+
+    match values.len() {
+        0 => Vec3::new(0.0, 0.0, 0.0),
+        1 | 2 => Vec3::new(values[0], values[0], values[0]),
+        _ => Vec3::new(values[0], values[1], values[2])
+    }
+}
+
+// Loads a texture map once per file, where linear maps hold data rather than sRGB colors
+fn mtl_texture(path: &Path, linear: bool, textures: &mut HashMap<(PathBuf, bool), Arc<dyn Texture>>) -> Result<Arc<dyn Texture>, ModelError> {
+    let key = (path.to_path_buf(), linear);
+    if let Some(texture) = textures.get(&key) {
+        return Ok(texture.clone())
+    }
+
+    let image = if linear { ImageTexture::open_linear(path, WrapMode::Repeat) } else { ImageTexture::open(path, WrapMode::Repeat) };
+    let texture: Arc<dyn Texture> = Arc::new(image.map_err(|e| ModelError::Texture(path.to_path_buf(), e))?);
+    textures.insert(key, texture.clone());
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{test_dir, Ray};
+
+    // One triangle per material, each further along x, with the MTL file holding the given materials
+    fn load(name: &str, materials: &[&str]) -> Result<Vec<Box<dyn Hitable>>, ModelError> {
+        let dir = test_dir(name);
+        let mut obj = String::from("mtllib test.mtl\n");
+        let mut mtl = String::new();
+        for (index, material) in materials.iter().enumerate() {
+            let x = 2.0 * index as f64;
+            obj += &format!("o model{}\nusemtl m{}\nv {} 0 0\nv {} 0 0\nv {} 1 0\nf -3 -2 -1\n", index, index, x, x + 1.0, x);
+            mtl += &format!("newmtl m{}\n{}\n\n", index, material);
+        }
+        fs::write(dir.join("test.obj"), obj).unwrap();
+        fs::write(dir.join("test.mtl"), mtl).unwrap();
+
+        let mut list = vec![];
+        load_model(&mut list, &dir.join("test.obj").to_string_lossy()).map(|_| list)
+    }
+
+    // Name of the variant each loaded model ended up with, in order
+    fn variants(name: &str, materials: &[&str]) -> Vec<&'static str> {
+        load(name, materials)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, model)| {
+                let r = Ray::new(Vec3::new(2.0 * index as f64 + 0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+                match model.hit(r, 0.001, f64::MAX).unwrap().1 {
+                    Material::Lambertian(_) => "lambertian",
+                    Material::Metal(_) => "metal",
+                    Material::Dielectric(_) => "dielectric",
+                    Material::Emission(_) => "emission",
+                    Material::Principled(_) => "principled",
+                    _ => "other"
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn illumination_models() {
+        let materials = ["illum 0", "illum 1", "Ks 0 0 0\nillum 2", "illum 3", "illum 5", "illum 8", "illum 4", "illum 6", "illum 7", "Ni 1.33\nillum 9"];
+        let expected = ["lambertian", "lambertian", "lambertian", "metal", "metal", "metal", "dielectric", "dielectric", "dielectric", "dielectric"];
+        assert_eq!(variants("mtl-illum", &materials), expected);
+    }
+
+    #[test]
+    fn emission_and_pbr_parameters() {
+        let materials = ["Ke 2 2 2\nillum 3", "Ke 0 0 0\nillum 1", "Pr 0.4\nillum 3", "Pm 1\nillum 4", "Ks 0.5 0.5 0.5\nillum 2", "d 0.5\nillum 1", "d 0.5\nillum 4"];
+        let expected = ["emission", "lambertian", "principled", "principled", "principled", "principled", "principled"];
+        assert_eq!(variants("mtl-pbr", &materials), expected);
+    }
+
+    #[test]
+    fn textures_are_relative_to_the_mtl_file() {
+        let dir = test_dir("mtl-textures");
+        fs::create_dir(dir.join("materials")).unwrap();
+        fs::write(dir.join("test.obj"), "mtllib materials/test.mtl\nusemtl wood\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n").unwrap();
+        fs::write(dir.join("materials/test.mtl"), "newmtl wood\nillum 1\nmap_Kd wood.png\n").unwrap();
+        let path = dir.join("test.obj").to_string_lossy().into_owned();
+
+        // Next to the OBJ file rather than the MTL file the texture is not found
+        image::RgbImage::from_pixel(1, 1, image::Rgb([200, 100, 50])).save(dir.join("wood.png")).unwrap();
+        match load_model(&mut vec![], &path) {
+            Err(ModelError::Texture(texture, _)) => assert_eq!(texture, dir.join("materials").join("wood.png")),
+            _ => panic!("expected the texture to be looked for next to the MTL file")
+        }
+
+        fs::rename(dir.join("wood.png"), dir.join("materials/wood.png")).unwrap();
+        let mut list = vec![];
+        load_model(&mut list, &path).unwrap();
+        assert_eq!(list.len(), 1);
+    }
+
+    // Loads an OBJ file with the given contents and no MTL file
+    fn load_obj(name: &str, obj: &str) -> Result<Vec<Box<dyn Hitable>>, ModelError> {
+        let dir = test_dir(name);
+        fs::write(dir.join("test.obj"), obj).unwrap();
+        let mut list = vec![];
+        load_model(&mut list, &dir.join("test.obj").to_string_lossy()).map(|_| list)
+    }
+
+    #[test]
+    fn missing_files() {
+        let dir = test_dir("model-missing");
+        match load_model(&mut vec![], &dir.join("none.obj").to_string_lossy()) {
+            Err(ModelError::NotFound(path)) => assert_eq!(path, dir.join("none.obj")),
+            other => panic!("expected the OBJ file to be missing, got {:?}", other.err())
+        }
+
+        match load_obj("model-missing-mtl", "mtllib none.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n") {
+            Err(ModelError::NotFound(path)) => assert!(path.ends_with("none.mtl")),
+            other => panic!("expected the MTL file to be missing, got {:?}", other.err())
+        }
+    }
+
+    #[test]
+    fn faces_referencing_missing_vertices() {
+        let cases = [
+            ("f 1 2 4", "positions"),
+            ("f -4 -2 -1", "positions"),
+            ("f 0 1 2", "positions"),
+            ("f 1/1 2/2 3/2", "texture coordinates"),
+            ("f 1//1 2//1 3//2", "normals")
+        ];
+        for (index, &(face, missing)) in cases.iter().enumerate() {
+            let obj = format!("o broken\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n{}\n", face);
+            match load_obj(&format!("model-face-{}", index), &obj) {
+                Err(ModelError::MissingAttribute { model, attribute }) => assert_eq!((model.as_str(), attribute), ("broken", missing), "{}", face),
+                other => panic!("{}: expected missing {}, got {:?}", face, missing, other.err())
+            }
+        }
+    }
+
+    #[test]
+    fn missing_materials() {
+        let dir = test_dir("model-missing-material");
+        fs::write(dir.join("test.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        fs::write(dir.join("test.obj"), "mtllib test.mtl\nusemtl blue\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let path = dir.join("test.obj").to_string_lossy().into_owned();
+        match load_model(&mut vec![], &path) {
+            Err(ModelError::MissingMaterial { model, material }) => assert_eq!((model.as_str(), material.as_str()), ("unnamed_object", "blue")),
+            other => panic!("expected the material to be missing, got {:?}", other.err())
+        }
+
+        // Replacing the material makes up for it
+        let mut list = vec![];
+        load_model_with(&mut list, &path, |_| Some(Material::Metal(Metal::new(Arc::new(ConstantTexture::new(Vec3::new(0.9, 0.9, 0.9))), 0.0)))).unwrap();
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn models_without_normals() {
+        // Shading falls back to the face normal, which follows the winding of the vertices
+        let list = load_obj("model-no-normals", "o flat\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(list.len(), 1);
+        for &(z, front_face) in [(1.0, true), (-1.0, false)].iter() {
+            let r = Ray::new(Vec3::new(0.25, 0.25, z), Vec3::new(0.0, 0.0, -z));
+            let (surface_interaction, material) = list[0].hit(r, 0.001, f64::MAX).unwrap();
+            assert_eq!(surface_interaction.front_face, front_face);
+            let n = surface_interaction.normal;
+            assert_eq!([n.x, n.y, n.z], [0.0, 0.0, 1.0]);
+            assert!(matches!(material, Material::Lambertian(_)));
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::util::Vec3;
+use std::io::{self, Write};
+
+// Portable float map: a text header giving the channel count, size and a scale whose sign is the
+// byte order, followed by 32-bit floats with the bottom row first
+pub fn read_pfm(data: &[u8]) -> image::ImageResult<(usize, usize, Vec<Vec3>)> {
+    let error = |message: &str| image::ImageError::FormatError(format!("invalid PFM file: {}", message));
+
+    // The header is four whitespace separated fields, ending in a single whitespace character
+    let mut fields = vec![];
+    let mut position = 0;
+    while fields.len() < 4 {
+        while data.get(position).is_some_and(u8::is_ascii_whitespace) {
+            position += 1;
+        }
+        let start = position;
+        while data.get(position).is_some_and(|c| !c.is_ascii_whitespace()) {
+            position += 1;
+        }
+        if start == position {
+            return Err(error("header ends early"))
+        }
+        fields.push(String::from_utf8_lossy(&data[start..position]).into_owned());
+    }
+    position += 1;
+
+    let channels = match fields[0].as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(error("expected PF or Pf"))
+    };
+    let width: usize = fields[1].parse().map_err(|_| error("bad width"))?;
+    let height: usize = fields[2].parse().map_err(|_| error("bad height"))?;
+    let scale: f64 = fields[3].parse().map_err(|_| error("bad scale"))?;
+
+    let body = data.get(position..).unwrap_or(&[]);
+    let size = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(channels * 4));
+    if size.is_none_or(|size| body.len() < size) {
+        return Err(image::ImageError::NotEnoughData)
+    }
+
+    let value = |index: usize| {
+        let bytes = [body[4 * index], body[4 * index + 1], body[4 * index + 2], body[4 * index + 3]];
+        let value = if scale < 0.0 { f32::from_le_bytes(bytes) } else { f32::from_be_bytes(bytes) };
+        value as f64
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let index = (y * width + x) * channels;
+            pixels.push(if channels == 3 {
+                Vec3::new(value(index), value(index + 1), value(index + 2))
+            } else {
+                Vec3::new(value(index), value(index), value(index))
+            });
+        }
+    }
+
+    Ok((width, height, pixels))
+}
+
+// Writes linear RGB pixels given top row first as a little-endian portable float map
+pub fn write_pfm<W: Write>(writer: &mut W, width: usize, height: usize, pixels: &[[f32; 3]]) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    let mut row = Vec::with_capacity(width * 12);
+    for y in (0..height).rev() {
+        row.clear();
+        for pixel in &pixels[y * width..(y + 1) * width] {
+            for channel in pixel {
+                row.extend_from_slice(&channel.to_le_bytes());
+            }
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three by two pixels, each with its own values, top row first
+    fn pixels() -> Vec<[f32; 3]> {
+        (0..6).map(|i| [i as f32, 0.5 + i as f32, -1.25 * i as f32]).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let pixels = pixels();
+        let mut data = vec![];
+        write_pfm(&mut data, 3, 2, &pixels).unwrap();
+
+        let (width, height, read) = read_pfm(&data).unwrap();
+        assert_eq!((width, height), (3, 2));
+        for (pixel, value) in pixels.iter().zip(read.iter()) {
+            assert_eq!([pixel[0] as f64, pixel[1] as f64, pixel[2] as f64], [value.x, value.y, value.z]);
+        }
+    }
+
+    #[test]
+    fn writes_bottom_row_first_little_endian() {
+        let mut data = vec![];
+        write_pfm(&mut data, 3, 2, &pixels()).unwrap();
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + 6 * 12);
+
+        // First comes the left pixel of the bottom row, which is the fourth given
+        let body = &data[header.len()..];
+        assert_eq!(&body[..4], &3.0f32.to_le_bytes());
+        assert_eq!(&body[4..8], &3.5f32.to_le_bytes());
+        assert_eq!(&body[36..40], &0.0f32.to_le_bytes());
+    }
+
+    #[test]
+    fn reads_big_endian_and_greyscale() {
+        let mut data = b"PF\n1 2\n1.0\n".to_vec();
+        for value in [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0].iter() {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        let (_, _, pixels) = read_pfm(&data).unwrap();
+        assert_eq!([pixels[0].x, pixels[0].y, pixels[0].z], [4.0, 5.0, 6.0]);
+        assert_eq!([pixels[1].x, pixels[1].y, pixels[1].z], [1.0, 2.0, 3.0]);
+
+        let mut data = b"Pf 2 1 -1.0\n".to_vec();
+        data.extend_from_slice(&7.0f32.to_le_bytes());
+        data.extend_from_slice(&8.0f32.to_le_bytes());
+        let (_, _, pixels) = read_pfm(&data).unwrap();
+        assert_eq!([pixels[1].x, pixels[1].y, pixels[1].z], [8.0, 8.0, 8.0]);
+    }
+
+    #[test]
+    fn rejects_short_data() {
+        let mut data = vec![];
+        write_pfm(&mut data, 3, 2, &pixels()).unwrap();
+        assert!(read_pfm(&data[..data.len() - 1]).is_err());
+        assert!(read_pfm(b"PF\n3").is_err());
+        assert!(read_pfm(b"P6\n3 2\n255\n").is_err());
+    }
+}
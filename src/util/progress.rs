@@ -0,0 +1,111 @@
+use crate::util::TileProgress;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 30;
+
+// Progress bar with the time elapsed and left, drawn on stderr. A terminal gets one line redrawn in
+// place a few times a second, while logs get a line every tenth of the way.
+pub struct ProgressBar {
+    terminal: bool,
+    state: Mutex<ProgressState>
+}
+
+struct ProgressState {
+    completed: usize,
+    last_draw: Option<Instant>,
+    last_tenth: usize,
+    finished: bool
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        ProgressBar::new()
+    }
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar {
+            terminal: io::stderr().is_terminal(),
+            state: Mutex::new(ProgressState {
+                completed: 0,
+                last_draw: None,
+                last_tenth: 0,
+                finished: false
+            })
+        }
+    }
+
+    pub fn update(&self, progress: &TileProgress) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Reports from threads finishing together can arrive out of order
+        if state.finished || progress.completed <= state.completed {
+            return
+        }
+        state.completed = progress.completed;
+
+        let done = progress.fraction >= 1.0;
+        let line = ProgressBar::line(progress);
+
+        if self.terminal {
+            if done || state.last_draw.is_none_or(|last| last.elapsed() >= Duration::from_millis(100)) {
+                eprint!("\r{}\x1b[K", line);
+                if done {
+                    eprintln!();
+                }
+                state.last_draw = Some(Instant::now());
+                state.finished = done;
+            }
+        } else {
+            let tenth = (progress.fraction * 10.0) as usize;
+            if tenth > state.last_tenth {
+                eprintln!("{}", line);
+                state.last_tenth = tenth;
+            }
+        }
+        let _ = io::stderr().flush();
+    }
+
+    // Ends the line left on a terminal by a render stopping short of the end
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if self.terminal && state.last_draw.is_some() && !state.finished {
+            eprintln!();
+            state.finished = true;
+        }
+    }
+
+    fn line(progress: &TileProgress) -> String {
+        let filled = (progress.fraction * BAR_WIDTH as f64) as usize;
+        let mut line = format!(
+            "[{}{}] {:3.0}% pass {}, {} elapsed",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            progress.fraction * 100.0,
+            progress.pass + 1,
+            format_duration(progress.elapsed)
+        );
+
+        if progress.fraction < 1.0 {
+            line += &format!(", about {} left", format_duration(progress.eta()));
+        }
+        line
+    }
+}
+
+// Hours, minutes and seconds, leaving out the leading ones that are zero
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
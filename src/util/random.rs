@@ -0,0 +1,39 @@
+use rand::{Error, RngCore, SeedableRng};
+use rand::rngs::SmallRng;
+use std::cell::RefCell;
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+// Handle to the random number generator of the current thread. Unlike `rand::thread_rng` the
+// generator can be reseeded, which makes renders reproducible when every unit of work seeds it.
+#[derive(Copy, Clone)]
+pub struct LocalRng;
+
+pub fn rng() -> LocalRng {
+    LocalRng
+}
+
+// Restarts the random sequence of the current thread from a seed
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
@@ -0,0 +1,320 @@
+use crate::objects::Scene;
+use crate::util::{rng, seed_rng, tiles, Camera, Film, FilmTile, RenderSettings, Sample, Tile};
+use rand::Rng;
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// Passed to the progress callback each time a tile has been merged into the film. Tiles finishing
+// on different threads at once may be reported slightly out of order.
+#[derive(Copy, Clone, Debug)]
+pub struct TileProgress {
+    pub tile: Tile,
+    // Pass the tile was rendered in, counting those before a resumed render
+    pub pass: usize,
+    // Tiles finished since the render was started or resumed
+    pub completed: usize,
+    // Share of the render done, by whichever of the sample count, time limit or noise target is
+    // closest to ending it
+    pub fraction: f64,
+    pub elapsed: Duration
+}
+
+impl TileProgress {
+    // Time left if the rest goes as fast as what is done
+    pub fn eta(&self) -> Duration {
+        if self.fraction <= 0.0 {
+            return Duration::from_secs(0)
+        }
+        self.elapsed.mul_f64((1.0 - self.fraction).max(0.0) / self.fraction)
+    }
+}
+
+// How far a progressive render has come: the passes done and the samples per pixel they took, which
+// pixels that reached the noise target before the last pass have fewer of
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct RenderState {
+    pub passes: usize,
+    pub samples: usize
+}
+
+// Renders a scene tile by tile on the rayon thread pool, with the tiles handed out in the order
+// chosen by the settings. The samples are taken in passes over the whole image, so it can be saved
+// between them and the render continued later. Passes go on until the image has the samples asked
+// for, the next pass would not end within the time limit, or every pixel is below the noise target.
+pub struct Renderer<'a> {
+    camera: &'a Camera,
+    scene: &'a Scene,
+    settings: RenderSettings,
+    seed: u64
+}
+
+impl<'a> Renderer<'a> {
+    // With a seed every pixel gets the same samples on every run, whatever the number of threads or
+    // passes, so the image only changes by the rounding of summing them in another order. Without
+    // one a seed is picked at random.
+    pub fn new(camera: &'a Camera, scene: &'a Scene, settings: RenderSettings, seed: Option<u64>) -> Renderer<'a> {
+        Renderer {
+            camera,
+            scene,
+            settings,
+            seed: seed.unwrap_or_else(rand::random)
+        }
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn tiles(&self) -> Vec<Tile> {
+        tiles(self.settings.width, self.settings.height, self.settings.tile_size, self.settings.tile_order)
+    }
+
+    pub fn render(&self) -> Film {
+        self.render_with_progress(|_| ())
+    }
+
+    // Calls `progress` from the render threads after each tile is done
+    pub fn render_with_progress<F: Fn(&TileProgress) + Sync>(&self, progress: F) -> Film {
+        let film = Film::new(self.settings.width, self.settings.height, self.settings.filter);
+        self.render_passes(&film, RenderState::default(), progress, |_, _| ());
+        film
+    }
+
+    // Adds passes to a film already holding those counted by `state` until the render is done. After
+    // each pass `on_pass` is called with the film and how far it has come. The time limit counts from
+    // the start of this call, which always takes at least one pass if any samples are left.
+    pub fn render_passes<F, P>(&self, film: &Film, state: RenderState, progress: F, mut on_pass: P) -> RenderState
+    where
+        F: Fn(&TileProgress) + Sync,
+        P: FnMut(&Film, RenderState)
+    {
+        let RenderSettings { samples, pass_samples, time_limit, noise_target, .. } = self.settings;
+        let pass_samples = pass_samples.max(1);
+        let tiles = self.tiles();
+        let passes = samples.saturating_sub(state.samples).div_ceil(pass_samples);
+        let time_limit = time_limit.map(Duration::from_secs_f64);
+        let completed = AtomicUsize::new(0);
+        let start = Instant::now();
+
+        let first_pass = state.passes;
+        let mut state = state;
+        let mut last_pass = Duration::from_secs(0);
+        while state.samples < samples {
+            // Passes are expected to take as long as the one before, which is as long or longer than
+            // the next when pixels reach the noise target. However short the limit, one pass is taken
+            // so the film is never left as it was.
+            if state.passes > first_pass && time_limit.is_some_and(|limit| start.elapsed() + last_pass > limit) {
+                break
+            }
+
+            let active = noise_target.map(|target| self.noisy_pixels(film, target));
+            let converged = match &active {
+                Some(active) => active.iter().filter(|&&noisy| !noisy).count() as f64 / active.len().max(1) as f64,
+                None => 0.0
+            };
+            if converged >= 1.0 {
+                break
+            }
+
+            let pass = state.passes;
+            let pass_start = Instant::now();
+            let pass_samples = pass_samples.min(samples - state.samples);
+
+            self.render_pass(film, &tiles, state.samples, pass_samples, active.as_deref(), |tile| {
+                let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                let elapsed = start.elapsed();
+                let by_samples = completed as f64 / (tiles.len() * passes).max(1) as f64;
+                let by_time = time_limit.map_or(0.0, |limit| elapsed.as_secs_f64() / limit.as_secs_f64());
+
+                progress(&TileProgress {
+                    tile,
+                    pass,
+                    completed,
+                    fraction: by_samples.max(by_time).max(converged).min(1.0),
+                    elapsed
+                });
+            });
+
+            last_pass = pass_start.elapsed();
+            state.passes += 1;
+            state.samples += pass_samples;
+            on_pass(film, state);
+        }
+        state
+    }
+
+    // Pixels to keep sampling, which are those with a pixel around them still above the noise target.
+    // Looking at the neighbours keeps a pixel whose few samples happened to agree from stopping early.
+    fn noisy_pixels(&self, film: &Film, target: f64) -> Vec<bool> {
+        let RenderSettings { width, height, .. } = self.settings;
+        let noise = film.noise_levels();
+
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let rows = y.saturating_sub(1)..(y + 2).min(height);
+                rows.flat_map(|ny| (x.saturating_sub(1)..(x + 2).min(width)).map(move |nx| ny * width + nx))
+                    .any(|index| noise[index] > target)
+            })
+            .collect()
+    }
+
+    // Takes the samples of one pass in the pixels marked active, or in all of them
+    fn render_pass<F: Fn(Tile) + Sync>(&self, film: &Film, tiles: &[Tile], first_sample: usize, samples: usize, active: Option<&[bool]>, done: F) {
+        let next = AtomicUsize::new(0);
+
+        // Every thread takes the next tile in line until none are left, which keeps to the order
+        // where splitting the list between the threads up front would not
+        (0..rayon::current_num_threads()).into_par_iter().for_each(|_| {
+            while let Some(&tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                film.merge(self.render_tile(film, tile, first_sample, samples, active));
+                done(tile);
+            }
+        });
+    }
+
+    // Takes the samples numbered from `first_sample` in the pixels of the tile
+    fn render_tile(&self, film: &Film, tile: Tile, first_sample: usize, samples: usize, active: Option<&[bool]>) -> FilmTile {
+        let RenderSettings { width, height, max_depth, roulette_depth, .. } = self.settings;
+        let mut film_tile = film.tile(tile.x0, tile.y0, tile.x1, tile.y1);
+
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                if active.is_some_and(|active| !active[y * width + x]) {
+                    continue
+                }
+
+                let mut rng = rng();
+                for s in first_sample..first_sample + samples {
+                    // Every sample of every pixel restarts the sequence from its own seed, so the image
+                    // depends on neither the tiles, how they are scheduled nor how the samples are split
+                    // into passes, and the seed and sample count are all a resumed render needs to carry
+                    // on with fresh samples
+                    let stream = (s as u64).wrapping_mul((width * height) as u64).wrapping_add((y * width + x) as u64);
+                    seed_rng(self.seed.wrapping_add(stream));
+
+                    let (film_x, film_y) = (x as f64 + rng.gen::<f64>(), y as f64 + rng.gen::<f64>());
+
+                    // The camera measures v upwards from the bottom of the image
+                    let r = self.camera.get_ray(film_x / width as f64, 1.0 - film_y / height as f64);
+                    let color = Sample::new(r, max_depth, roulette_depth, self.scene).last().unwrap();
+                    film_tile.add_sample(film_x, film_y, color);
+                }
+            }
+        }
+        film_tile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{simple_scene, Checkpoint};
+
+    const WIDTH: usize = 24;
+    const HEIGHT: usize = 12;
+
+    fn settings(samples: usize, pass_samples: usize) -> RenderSettings {
+        RenderSettings {
+            width: WIDTH,
+            height: HEIGHT,
+            samples,
+            pass_samples,
+            max_depth: 8,
+            tile_size: 5,
+            ..RenderSettings::default()
+        }
+    }
+
+    fn assert_same(a: &Film, b: &Film) {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let (pa, pb) = (a.pixel(x, y), b.pixel(x, y));
+                assert_eq!([pa.x, pa.y, pa.z], [pb.x, pb.y, pb.z], "pixel ({}, {})", x, y);
+                assert_eq!(a.samples(x, y), b.samples(x, y), "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let (camera, scene) = simple_scene(WIDTH, HEIGHT).unwrap();
+        let uninterrupted = Renderer::new(&camera, &scene, settings(8, 4), Some(7)).render();
+
+        // Stop after the first pass, keep the film only as a checkpoint and carry on from that
+        let first = Renderer::new(&camera, &scene, settings(4, 4), Some(7));
+        let film = first.render();
+        let checkpoint = Checkpoint {
+            scene: "simple".to_string(),
+            settings: first.settings().clone(),
+            seed: first.seed(),
+            state: RenderState {passes: 1, samples: 4}
+        };
+        let mut bytes = vec![];
+        checkpoint.write(&mut bytes, &film).unwrap();
+        let (checkpoint, resumed) = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+
+        let renderer = Renderer::new(&camera, &scene, settings(8, 4), Some(checkpoint.seed));
+        let state = renderer.render_passes(&resumed, checkpoint.state, |_| (), |_, _| ());
+        assert_eq!(state, RenderState {passes: 2, samples: 8});
+        assert_same(&resumed, &uninterrupted);
+    }
+
+    #[test]
+    fn pass_size_does_not_change_image() {
+        let (camera, scene) = simple_scene(WIDTH, HEIGHT).unwrap();
+        let whole = Renderer::new(&camera, &scene, settings(6, 6), Some(3)).render();
+        let split = Renderer::new(&camera, &scene, settings(6, 4), Some(3)).render();
+        let single = Renderer::new(&camera, &scene, settings(6, 1), Some(3)).render();
+
+        // Sums of the same samples taken over other passes round differently
+        for film in [&split, &single].iter() {
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let (a, b) = (whole.pixel(x, y), film.pixel(x, y));
+                    assert!((a - b).length() <= 1e-9 * (1.0 + a.length()), "pixel ({}, {})", x, y);
+                    assert_eq!(whole.samples(x, y), film.samples(x, y));
+                }
+            }
+        }
+    }
+
+    // As many samples as the command line asks for when only the time limit or noise target is meant to end the render
+    const UNLIMITED_SAMPLES: usize = u32::MAX as usize;
+
+    #[test]
+    fn noise_target_ends_render() {
+        let (camera, scene) = simple_scene(WIDTH, HEIGHT).unwrap();
+        let renderer = Renderer::new(&camera, &scene, RenderSettings {noise_target: Some(0.1), ..settings(UNLIMITED_SAMPLES, 8)}, Some(5));
+        let film = Film::new(WIDTH, HEIGHT, renderer.settings().filter);
+        let state = renderer.render_passes(&film, RenderState::default(), |_| (), |_, _| ());
+        assert!(state.samples < UNLIMITED_SAMPLES);
+        assert_eq!(state.samples, state.passes * 8);
+        assert!(film.noise_levels().iter().all(|&noise| noise <= 0.1));
+
+        // The pixels around the reflections and shadows are sampled to the end, while the smooth
+        // sky stops after a pass or two
+        let counts: Vec<u32> = (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (x, y))).map(|(x, y)| film.samples(x, y)).collect();
+        assert_eq!(*counts.iter().max().unwrap() as usize, state.samples);
+        assert!((*counts.iter().min().unwrap() as usize) < state.samples / 10, "{:?}", counts);
+    }
+
+    #[test]
+    fn time_limit_takes_one_pass() {
+        let (camera, scene) = simple_scene(WIDTH, HEIGHT).unwrap();
+        let renderer = Renderer::new(&camera, &scene, RenderSettings {time_limit: Some(1e-9), ..settings(UNLIMITED_SAMPLES, 4)}, Some(5));
+        let film = Film::new(WIDTH, HEIGHT, renderer.settings().filter);
+        let state = renderer.render_passes(&film, RenderState::default(), |_| (), |_, _| ());
+        assert_eq!(state, RenderState {passes: 1, samples: 4});
+        assert!((0..HEIGHT).all(|y| (0..WIDTH).all(|x| film.samples(x, y) == 4)));
+
+        // The same holds when carrying on from an earlier render
+        let state = renderer.render_passes(&film, state, |_| (), |_, _| ());
+        assert_eq!(state, RenderState {passes: 2, samples: 8});
+    }
+}
@@ -1,22 +1,34 @@
-use crate::util::{Ray, Vec3};
-use crate::objects::Hitable;
+use rand::Rng;
+use crate::util::{power_heuristic, rng, Ray, Vec3};
+use crate::objects::Scene;
 
 pub struct Sample<'a> {
     ray: Option<Ray>,
+    // Fraction of the light found further along the path that reaches the camera
+    throughput: Vec3,
     color: Vec3,
+    // Density with which the material at the previous bounce picked the current ray, or None when
+    // that bounce was specular and the lights were not sampled there
+    scatter_pdf: Option<f64>,
     depth: usize,
     max_depth: usize,
-    world: &'a dyn Hitable
+    // Bounces after which paths are randomly terminated based on their throughput
+    roulette_depth: usize,
+    scene: &'a Scene
 }
 
 impl<'a> Sample<'a> {
-    pub fn new(ray: Ray, max_depth: usize, world: &'a dyn Hitable) -> Self {
+    // Passing a roulette depth of at least max_depth turns Russian roulette off
+    pub fn new(ray: Ray, max_depth: usize, roulette_depth: usize, scene: &'a Scene) -> Self {
         Sample {
             ray: Some(ray),
-            color: Vec3::new(1.0, 1.0, 1.0),
+            throughput: Vec3::new(1.0, 1.0, 1.0),
+            color: Vec3::new(0.0, 0.0, 0.0),
+            scatter_pdf: None,
             depth: 0,
             max_depth,
-            world
+            roulette_depth,
+            scene
         }
     }
 }
@@ -26,41 +38,102 @@ impl<'a> Iterator for Sample<'a> {
 
     // Propagates the ray through the scene to get the color of the sample
     fn next(&mut self) -> Option<Vec3> {
-        match self.ray {
-            Some(ray) => {
-                // t_min here is set to 0.001 to prevent some shadowing errors
-                match self.world.hit(ray, 0.001, std::f64::INFINITY) {
-                    Some((surface_interaction, material)) => {
-                        match material.scatter(ray, surface_interaction.normal, surface_interaction.point) {
-                            (attenuation, None) => {
-                                self.color *= attenuation;
-                                self.ray = None;
-                            }
-                            (attenuation, Some(scattered)) => {
-                                self.color *= attenuation;
-
-                                if self.depth < self.max_depth {
-                                    self.depth += 1;
-                                    self.ray = Some(scattered);
-                                } else {
-                                    self.ray = None
-                                }
-                            }
-                        }
+        let ray = self.ray.take()?;
+
+        // t_min here is set to 0.001 to prevent some shadowing errors
+        let hit = self.scene.hit(ray, 0.001, f64::INFINITY);
+
+        // Lights with an area are not part of the world, so the ray is checked against them up to
+        // the nearest object. They absorb whatever reaches them.
+        let t_max = hit.as_ref().map_or(f64::INFINITY, |(surface_interaction, _)| surface_interaction.t);
+        if let Some((light, radiance)) = self.scene.hit_light(ray, 0.001, t_max) {
+            let weight = match self.scatter_pdf {
+                Some(scatter_pdf) => power_heuristic(scatter_pdf, self.scene.light_pdf(light, ray.origin, ray.direction)),
+                None => 1.0
+            };
+            self.color += self.throughput * radiance * weight;
+            return Some(self.color)
+        }
+
+        match hit {
+            Some((surface_interaction, material)) => {
+                let primitive = surface_interaction.primitive;
+
+                // Light found by following the material is weighted against the chance that
+                // sampling the lights at the previous bounce picked the same direction
+                let weight = match self.scatter_pdf {
+                    Some(scatter_pdf) if primitive.is_light() => {
+                        power_heuristic(scatter_pdf, self.scene.emitter_pdf(primitive, ray.origin, ray.direction))
                     }
-                    None => {
-                        // This is the sky color and falloff
-                        let unit_direction: Vec3 = ray.direction.unit();
-                        let t: f64 = 0.5 * (unit_direction.y + 1.0);
-                        self.color *= Vec3::new(1.0, 1.0, 1.0) * (1.0 - t) + Vec3::new(0.5, 0.7, 1.0) * t;
-                        self.ray = None;
+                    _ => 1.0
+                };
+                self.color += self.throughput * material.emitted() * weight;
+
+                if !material.is_specular() {
+                    self.color += self.throughput * self.scene.sample_lights(ray, &surface_interaction, material);
+                }
+
+                if let Some(sample) = material.sample(ray, &surface_interaction) {
+                    self.scatter_pdf = if sample.specular { None } else { Some(sample.pdf) };
+                    self.throughput *= sample.weight;
+
+                    // Paths carrying little light are ended early, and the survivors are scaled up
+                    // to make up for the ones that were dropped
+                    let survival = if self.depth >= self.roulette_depth {
+                        self.throughput.x.max(self.throughput.y).max(self.throughput.z).min(1.0)
+                    } else {
+                        1.0
+                    };
+
+                    if self.depth < self.max_depth && (survival >= 1.0 || rng().gen::<f64>() < survival) {
+                        self.throughput = self.throughput / survival;
+                        self.depth += 1;
+                        self.ray = Some(Ray::new(surface_interaction.point, sample.direction));
                     }
                 }
-                Some(self.color)
             }
             None => {
-                None
+                let direction = ray.direction.unit();
+                let weight = match self.scatter_pdf {
+                    Some(scatter_pdf) => power_heuristic(scatter_pdf, self.scene.background_pdf(direction)),
+                    None => 1.0
+                };
+                self.color += self.throughput * self.scene.background().radiance(direction) * weight;
             }
         }
+        Some(self.color)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{simple_scene, Film, RenderSettings, Renderer, Vec3};
+
+    // Radiance averaged over the image
+    fn mean(film: &Film) -> Vec3 {
+        let mut total = Vec3::new(0.0, 0.0, 0.0);
+        for y in 0..film.height() {
+            for x in 0..film.width() {
+                total += film.pixel(x, y);
+            }
+        }
+        total / (film.width() * film.height()) as f64
+    }
+
+    // Russian roulette only changes how many paths carry the light, not how much they carry on
+    // average. Both renders use the same seed and so start from the same camera rays, which keeps
+    // their means within half a percent of each other, where other seeds move it by over one.
+    #[test]
+    fn roulette_is_unbiased() {
+        let (camera, scene) = simple_scene(32, 16).unwrap();
+        let render = |roulette_depth| {
+            let settings = RenderSettings {width: 32, height: 16, samples: 64, pass_samples: 64, max_depth: 16, roulette_depth, tile_size: 16, ..RenderSettings::default()};
+            mean(&Renderer::new(&camera, &scene, settings, Some(1)).render())
+        };
+
+        let (without, with) = (render(16), render(0));
+        for c in 0..3 {
+            assert!((with[c] - without[c]).abs() < 0.005 * without[c], "channel {}: {} with roulette against {} without", c, with[c], without[c]);
+        }
+    }
+}
@@ -0,0 +1,839 @@
+use crate::objects::{Background, Bvh, ConstantBackground, EnvironmentMap, GradientBackground, Hitable, Scene, Sphere, Triangle};
+use crate::light::{DirectionalLight, DiskLight, Light, PointLight, RectLight, SpotLight};
+use crate::material::{complex_ior, Conductor, Dielectric, Emission, Lambertian, Material, Metal, Principled, RoughDielectric};
+use crate::texture::{CheckerTexture, ConstantTexture, ImageTexture, NoiseTexture, Texture, WrapMode};
+use crate::util::{load_model_with, random_material_description, rng, Camera, Filter, ModelError, TileOrder, ToneMapOperator, Vec3};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Declarative description of a scene which can be read from and written to TOML. For example:
+//
+//     [camera]
+//     look_from = [13.0, 2.0, 3.0]
+//     look_at = [0.0, 1.0, 0.0]
+//     fov = 20.0
+//
+//     [render]
+//     width = 800
+//     height = 400
+//
+//     [materials.red]
+//     type = "lambertian"
+//     attenuation = [0.7, 0.1, 0.1]
+//
+//     [[objects]]
+//     type = "sphere"
+//     center = [0.0, 1.0, 0.0]
+//     radius = 1.0
+//     material = "red"
+//
+// Materials may be given inline instead of by name, and "random" draws a new material every time
+// it is used. Paths to meshes, textures and environment maps are relative to the scene file. Without
+// a [background] table the scene is lit by the sky gradient. Lights are listed as [[lights]] tables:
+//
+//     [[lights]]
+//     type = "spot"
+//     position = [0.0, 5.0, 0.0]
+//     look_at = [0.0, 0.0, 0.0]
+//     intensity = [50.0, 50.0, 50.0]
+//     angle = 30.0
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub background: BackgroundDescription,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightDescription>,
+    #[serde(skip)]
+    pub base_dir: PathBuf
+}
+
+// Parameters of Camera::new, with the aspect ratio taken from the rendered image
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CameraDescription {
+    pub look_from: [f64; 3],
+    pub look_at: [f64; 3],
+    #[serde(default = "default_up")]
+    pub up: [f64; 3],
+    pub fov: f64,
+    #[serde(default)]
+    pub aperture: f64,
+    // Defaults to the distance between look_from and look_at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f64>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples: usize,
+    // Samples per pixel added to the whole image at a time, between which it can be saved
+    pub pass_samples: usize,
+    // Seconds the render may take, and the relative noise at which pixels stop being sampled, which
+    // both end a render before it has all its samples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_target: Option<f64>,
+    pub max_depth: usize,
+    // Bounces before paths may be ended by Russian roulette
+    pub roulette_depth: usize,
+    // How 8-bit images are made from the radiance, with the exposure in stops
+    pub tone_map: ToneMapOperator,
+    pub exposure: f64,
+    // Pixels along the side of the tiles handed to the render threads, and the order they go in
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    // Reconstruction filter, kept last as it is written as a table
+    pub filter: Filter
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 2400,
+            height: 800,
+            samples: 128,
+            pass_samples: 16,
+            time_limit: None,
+            noise_target: None,
+            max_depth: 50,
+            roulette_depth: 3,
+            tone_map: ToneMapOperator::Clamp,
+            exposure: 0.0,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            filter: Filter::default()
+        }
+    }
+}
+
+// What rays leaving the scene see, which is also light falling onto it
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackgroundDescription {
+    Color {
+        color: [f64; 3]
+    },
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3]
+    },
+    // Equirectangular image, either a Radiance .hdr file or an sRGB image
+    Image {
+        path: String,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        // Degrees turned around the up axis
+        #[serde(default)]
+        rotation: f64
+    }
+}
+
+impl Default for BackgroundDescription {
+    fn default() -> Self {
+        BackgroundDescription::Gradient {
+            bottom: [1.0, 1.0, 1.0],
+            top: [0.5, 0.7, 1.0]
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialDescription {
+    Lambertian {
+        attenuation: TextureDescription
+    },
+    Metal {
+        #[serde(default)]
+        fuzziness: f64,
+        attenuation: TextureDescription
+    },
+    // Microfacet metal, either one of the measured metals of complex_ior or a given eta and k
+    Conductor {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metal: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        eta: Option<[f64; 3]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        k: Option<[f64; 3]>,
+        #[serde(default)]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64
+    },
+    Dielectric {
+        refraction: f64
+    },
+    RoughDielectric {
+        refraction: f64,
+        #[serde(default)]
+        roughness: f64
+    },
+    Principled(Box<PrincipledDescription>),
+    Emission {
+        color: [f64; 3]
+    },
+    Random
+}
+
+// Layered material where every parameter left out takes the default of Principled::new
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PrincipledDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refraction: Option<f64>,
+    pub base_color: TextureDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metallic: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roughness: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub specular: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sheen: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearcoat: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearcoat_roughness: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transmission: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subsurface: Option<TextureDescription>
+}
+
+// Either the name of an entry in the materials table or a material given in place
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum MaterialRef {
+    Named(String),
+    Inline(MaterialDescription)
+}
+
+// Either a plain value, a plain color or one of the texture types
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TextureDescription {
+    Value(f64),
+    Color([f64; 3]),
+    Texture(TextureKind)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextureKind {
+    Checker {
+        scale: f64,
+        // Checks across the texture coordinates rather than in world space
+        #[serde(default)]
+        uv: bool,
+        even: Box<TextureDescription>,
+        odd: Box<TextureDescription>
+    },
+    Image {
+        path: String,
+        #[serde(default)]
+        wrap: WrapDescription,
+        // Set for maps holding data such as roughness, which are not sRGB encoded
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        linear: bool
+    },
+    Noise {
+        color: [f64; 3],
+        scale: f64
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapDescription {
+    #[default]
+    Repeat,
+    Mirror,
+    Clamp
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: MaterialRef
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        // Defaults to the geometric normal of the vertices in counter-clockwise order
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normal: Option<[f64; 3]>,
+        material: MaterialRef
+    },
+    Mesh {
+        path: String,
+        // Replaces the materials from the MTL file for every model in the mesh
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+        // Replaces the material of models by name, where a trailing '*' matches any suffix
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        overrides: BTreeMap<String, MaterialRef>
+    }
+}
+
+// Lights placed on their own. Point and spot lights give their intensity, the power sent into each
+// unit of solid angle, the directional light the irradiance on a surface facing it, and the
+// rectangle and disk the radiance leaving the side facing their look_at point.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LightDescription {
+    Point {
+        position: [f64; 3],
+        intensity: [f64; 3]
+    },
+    Spot {
+        position: [f64; 3],
+        look_at: [f64; 3],
+        intensity: [f64; 3],
+        // Half-width of the cone in degrees, the last `falloff` degrees of which fade out
+        angle: f64,
+        #[serde(default)]
+        falloff: f64
+    },
+    Directional {
+        // Direction the light travels in
+        direction: [f64; 3],
+        irradiance: [f64; 3]
+    },
+    Rect {
+        center: [f64; 3],
+        look_at: [f64; 3],
+        width: f64,
+        height: f64,
+        color: [f64; 3]
+    },
+    Disk {
+        center: [f64; 3],
+        look_at: [f64; 3],
+        radius: f64,
+        color: [f64; 3]
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    // The file parsed but describes something that cannot be built
+    Invalid {
+        line: Option<usize>,
+        message: String
+    },
+    Model(ModelError),
+    Texture(PathBuf, image::ImageError)
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            SceneError::Parse(e) => write!(f, "invalid scene file: {}", e),
+            SceneError::Serialize(e) => write!(f, "could not write scene: {}", e),
+            SceneError::Invalid { line: Some(line), message } => write!(f, "line {}: {}", line, message),
+            SceneError::Invalid { line: None, message } => write!(f, "{}", message),
+            SceneError::Model(e) => write!(f, "{}", e),
+            SceneError::Texture(path, e) => write!(f, "could not load texture {}: {}", path.display(), e)
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io(_, e) => Some(e),
+            SceneError::Parse(e) => Some(e),
+            SceneError::Serialize(e) => Some(e),
+            SceneError::Model(e) => Some(e),
+            SceneError::Texture(_, e) => Some(e),
+            SceneError::Invalid { .. } => None
+        }
+    }
+}
+
+impl From<ModelError> for SceneError {
+    fn from(e: ModelError) -> Self {
+        SceneError::Model(e)
+    }
+}
+
+pub fn load_scene_file<P: AsRef<Path>>(path: P) -> Result<SceneDescription, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+
+    let mut description = SceneDescription::from_toml(&source)?;
+    description.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    Ok(description)
+}
+
+impl SceneDescription {
+    // Parses and checks a scene, with paths resolved relative to the working directory
+    pub fn from_toml(source: &str) -> Result<SceneDescription, SceneError> {
+        let description: SceneDescription = toml::from_str(source).map_err(SceneError::Parse)?;
+        description.validate(source)?;
+        Ok(description)
+    }
+
+    pub fn to_toml(&self) -> Result<String, SceneError> {
+        // Fields are declared with plain values ahead of tables so they serialize in order, but a
+        // mix of named and inline materials among overrides still needs the sorted Value output
+        toml::to_string(self)
+            .or_else(|_| toml::Value::try_from(self).map(|value| value.to_string()))
+            .map_err(SceneError::Serialize)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_toml()?).map_err(|e| SceneError::Io(path.to_path_buf(), e))
+    }
+
+    // Checks what parsing cannot, pointing at the table the problem was found in
+    fn validate(&self, source: &str) -> Result<(), SceneError> {
+        let invalid = |line, message| Err(SceneError::Invalid { line, message });
+
+        if self.render.width == 0 || self.render.height == 0 || self.render.samples == 0 {
+            return invalid(find_line(source, "[render]", 0), "width, height and samples must be greater than zero".to_string())
+        }
+
+        if self.render.tile_size == 0 || self.render.pass_samples == 0 {
+            return invalid(find_line(source, "[render]", 0), "tile size and pass samples must be greater than zero".to_string())
+        }
+
+        if self.render.time_limit.is_some_and(|limit| limit <= 0.0) || self.render.noise_target.is_some_and(|target| target <= 0.0) {
+            return invalid(find_line(source, "[render]", 0), "time limit and noise target must be positive".to_string())
+        }
+
+        let filter = self.render.filter;
+        if filter.radius() <= 0.0 || matches!(filter, Filter::Lanczos { tau, .. } if tau <= 0.0) {
+            return invalid(find_line(source, "[render.filter]", 0), "filter radius and tau must be positive".to_string())
+        }
+
+        if let BackgroundDescription::Image { intensity, .. } = self.background {
+            if intensity < 0.0 {
+                return invalid(find_line(source, "[background]", 0), format!("background has negative intensity {}", intensity))
+            }
+        }
+
+        for (name, material) in self.materials.iter() {
+            let line = find_line(source, &format!("[materials.{}]", name), 0)
+                .or_else(|| find_line(source, &format!("[materials.\"{}\"]", name), 0));
+
+            if let Err(message) = check_material(material) {
+                return invalid(line, format!("material '{}' {}", name, message))
+            }
+        }
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let line = find_line(source, "[[objects]]", index);
+
+            let references = match object {
+                ObjectDescription::Sphere { radius, material, .. } => {
+                    if *radius <= 0.0 {
+                        return invalid(line, format!("sphere radius must be positive, not {}", radius))
+                    }
+                    vec![material]
+                }
+                ObjectDescription::Triangle { material, .. } => vec![material],
+                ObjectDescription::Mesh { material, overrides, .. } => material.iter().chain(overrides.values()).collect()
+            };
+
+            for reference in references {
+                let result = match reference {
+                    MaterialRef::Named(name) if !self.materials.contains_key(name) => Err(format!("unknown material '{}'", name)),
+                    MaterialRef::Named(_) => Ok(()),
+                    MaterialRef::Inline(material) => check_material(material).map_err(|message| format!("material {}", message))
+                };
+
+                if let Err(message) = result {
+                    return invalid(line, format!("object {}: {}", index + 1, message))
+                }
+            }
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            if let Err(message) = check_light(light) {
+                return invalid(find_line(source, "[[lights]]", index), format!("light {}: {}", index + 1, message))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self, width: usize, height: usize) -> Camera {
+        let c = &self.camera;
+        let look_from = vec3(c.look_from);
+        let look_at = vec3(c.look_at);
+        let focus_distance = c.focus_distance.unwrap_or_else(|| (look_from - look_at).length());
+
+        Camera::new(look_from, look_at, vec3(c.up), c.fov, width as f64 / height as f64, c.aperture, focus_distance)
+    }
+
+    pub fn build(&self, width: usize, height: usize) -> Result<(Camera, Scene), SceneError> {
+        let mut builder = Builder {
+            description: self,
+            textures: HashMap::new(),
+            materials: HashMap::new()
+        };
+
+        let mut list: Vec<Box<dyn Hitable>> = vec![];
+        for object in self.objects.iter() {
+            builder.add_object(&mut list, object)?;
+        }
+
+        let lights = self.lights.iter().map(build_light).collect();
+        let background = self.build_background()?;
+        Ok((self.camera(width, height), Scene::with_lights(Bvh::new(list), lights, background)))
+    }
+
+    fn build_background(&self) -> Result<Box<dyn Background>, SceneError> {
+        let background: Box<dyn Background> = match &self.background {
+            BackgroundDescription::Color { color } => Box::new(ConstantBackground::new(vec3(*color))),
+            BackgroundDescription::Gradient { bottom, top } => Box::new(GradientBackground::new(vec3(*bottom), vec3(*top))),
+            BackgroundDescription::Image { path, intensity, rotation } => {
+                let path = self.base_dir.join(path);
+                let map = EnvironmentMap::open(&path, *intensity, *rotation).map_err(|e| SceneError::Texture(path, e))?;
+                Box::new(map)
+            }
+        };
+        Ok(background)
+    }
+}
+
+fn default_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+fn vec3(a: [f64; 3]) -> Vec3 {
+    Vec3::new(a[0], a[1], a[2])
+}
+
+// One-based line of the nth line starting with a table header
+fn find_line(source: &str, header: &str, nth: usize) -> Option<usize> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with(header))
+        .nth(nth)
+        .map(|(index, _)| index + 1)
+}
+
+fn check_material(material: &MaterialDescription) -> Result<(), String> {
+    match material {
+        MaterialDescription::Metal { fuzziness, .. } if *fuzziness < 0.0 => Err(format!("has negative fuzziness {}", fuzziness)),
+        MaterialDescription::Dielectric { refraction } | MaterialDescription::RoughDielectric { refraction, .. } if *refraction <= 0.0 => {
+            Err(format!("has non-positive refraction {}", refraction))
+        }
+        MaterialDescription::Principled(principled) => match principled.refraction {
+            Some(refraction) if refraction <= 0.0 => Err(format!("has non-positive refraction {}", refraction)),
+            _ => Ok(())
+        },
+        MaterialDescription::RoughDielectric { roughness, .. } if !(0.0..=1.0).contains(roughness) => Err(format!("has roughness {} outside [0, 1]", roughness)),
+        MaterialDescription::Conductor { roughness, anisotropy, .. } if !(0.0..=1.0).contains(roughness) || !(0.0..=1.0).contains(anisotropy) => {
+            Err(format!("has roughness {} or anisotropy {} outside [0, 1]", roughness, anisotropy))
+        }
+        MaterialDescription::Conductor { metal, eta, k, .. } => match (metal, eta, k) {
+            (Some(metal), None, None) if complex_ior(metal).is_some() => Ok(()),
+            (Some(metal), None, None) => Err(format!("has unknown metal '{}', expected gold, copper, aluminium or silver", metal)),
+            (None, Some(_), Some(_)) => Ok(()),
+            _ => Err("needs either a metal or both eta and k".to_string())
+        },
+        _ => Ok(())
+    }
+}
+
+fn check_light(light: &LightDescription) -> Result<(), String> {
+    match light {
+        LightDescription::Spot { position, look_at, .. } | LightDescription::Rect { center: position, look_at, .. } | LightDescription::Disk { center: position, look_at, .. }
+            if position == look_at => Err("looks at its own position".to_string()),
+        LightDescription::Spot { angle, falloff, .. } if !(*angle > 0.0 && *angle <= 180.0 && (0.0..=*angle).contains(falloff)) => {
+            Err(format!("has angle {} outside (0, 180] or falloff {} outside [0, angle]", angle, falloff))
+        }
+        LightDescription::Directional { direction, .. } if *direction == [0.0; 3] => Err("has no direction".to_string()),
+        LightDescription::Rect { width, height, .. } if *width <= 0.0 || *height <= 0.0 => Err(format!("has non-positive size {} by {}", width, height)),
+        LightDescription::Disk { radius, .. } if *radius <= 0.0 => Err(format!("has non-positive radius {}", radius)),
+        _ => Ok(())
+    }
+}
+
+fn build_light(light: &LightDescription) -> Box<dyn Light> {
+    match light {
+        LightDescription::Point { position, intensity } => Box::new(PointLight::new(vec3(*position), vec3(*intensity))),
+        LightDescription::Spot { position, look_at, intensity, angle, falloff } => {
+            Box::new(SpotLight::new(vec3(*position), vec3(*look_at) - vec3(*position), vec3(*intensity), *angle, *falloff))
+        }
+        LightDescription::Directional { direction, irradiance } => Box::new(DirectionalLight::new(vec3(*direction), vec3(*irradiance))),
+        LightDescription::Rect { center, look_at, width, height, color } => {
+            Box::new(RectLight::centered(vec3(*center), vec3(*look_at) - vec3(*center), *width, *height, vec3(*color)))
+        }
+        LightDescription::Disk { center, look_at, radius, color } => Box::new(DiskLight::new(vec3(*center), vec3(*look_at) - vec3(*center), *radius, vec3(*color)))
+    }
+}
+
+// Turns descriptions into scene objects, sharing textures and named materials between uses
+struct Builder<'a> {
+    description: &'a SceneDescription,
+    // Images keyed by path and whether they are linear
+    textures: HashMap<(PathBuf, bool), Arc<dyn Texture>>,
+    materials: HashMap<String, Material>
+}
+
+impl<'a> Builder<'a> {
+    fn add_object(&mut self, list: &mut Vec<Box<dyn Hitable>>, object: &ObjectDescription) -> Result<(), SceneError> {
+        match object {
+            ObjectDescription::Sphere { center, radius, material } => {
+                let material = self.material(material)?;
+                list.push(Box::new(Sphere::new(vec3(*center), *radius, material)));
+            }
+            ObjectDescription::Triangle { vertices, normal, material } => {
+                let (v0, v1, v2) = (vec3(vertices[0]), vec3(vertices[1]), vec3(vertices[2]));
+                let normal = match normal {
+                    Some(normal) => vec3(*normal).unit(),
+                    None => (v1 - v0).cross(v2 - v0).unit()
+                };
+
+                let material = self.material(material)?;
+                list.push(Box::new(Triangle::new(v0, v1, v2, normal, material)));
+            }
+            ObjectDescription::Mesh { path, material, overrides } => {
+                // Materials are resolved up front so that only random ones are drawn per model
+                let mut replacements: Vec<(&str, Option<Material>)> = vec![];
+                for (pattern, reference) in overrides.iter().map(|(p, r)| (p.as_str(), r)).chain(material.iter().map(|r| ("*", r))) {
+                    let replacement = if self.is_random(reference) { None } else { Some(self.material(reference)?) };
+                    replacements.push((pattern, replacement));
+                }
+
+                let path = self.description.base_dir.join(path);
+                load_model_with(list, &path.to_string_lossy(), |name| {
+                    let (_, replacement) = replacements.iter().find(|(pattern, _)| match pattern.strip_suffix('*') {
+                        Some(prefix) => name.starts_with(prefix),
+                        None => name == *pattern
+                    })?;
+
+                    match replacement {
+                        Some(material) => Some(material.clone()),
+                        None => self.build_material(&MaterialDescription::Random).ok()
+                    }
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_random(&self, reference: &MaterialRef) -> bool {
+        match reference {
+            MaterialRef::Inline(MaterialDescription::Random) => true,
+            MaterialRef::Named(name) => matches!(self.description.materials.get(name), Some(MaterialDescription::Random)),
+            MaterialRef::Inline(_) => false
+        }
+    }
+
+    fn material(&mut self, reference: &MaterialRef) -> Result<Material, SceneError> {
+        match reference {
+            MaterialRef::Inline(material) => self.build_material(material),
+            MaterialRef::Named(name) => {
+                if let Some(material) = self.materials.get(name) {
+                    return Ok(material.clone())
+                }
+
+                let description = self.description.materials.get(name).ok_or_else(|| SceneError::Invalid {
+                    line: None,
+                    message: format!("unknown material '{}'", name)
+                })?;
+
+                // Random materials are drawn again for every object that uses them
+                let material = self.build_material(description)?;
+                if !matches!(description, MaterialDescription::Random) {
+                    self.materials.insert(name.clone(), material.clone());
+                }
+                Ok(material)
+            }
+        }
+    }
+
+    fn build_material(&mut self, material: &MaterialDescription) -> Result<Material, SceneError> {
+        let material = match material {
+            MaterialDescription::Lambertian { attenuation } => Material::Lambertian(Lambertian::new(self.texture(attenuation)?)),
+            MaterialDescription::Metal { attenuation, fuzziness } => Material::Metal(Metal::new(self.texture(attenuation)?, *fuzziness)),
+            MaterialDescription::Conductor { metal, eta, k, roughness, anisotropy } => {
+                let (eta, k) = match (metal.as_deref().and_then(complex_ior), eta, k) {
+                    (Some(ior), _, _) => ior,
+                    (None, Some(eta), Some(k)) => (vec3(*eta), vec3(*k)),
+                    _ => return Err(SceneError::Invalid { line: None, message: "conductor needs either a known metal or both eta and k".to_string() })
+                };
+                Material::Conductor(Conductor::new(eta, k, *roughness, *anisotropy))
+            }
+            MaterialDescription::Dielectric { refraction } => Material::Dielectric(Dielectric::new(*refraction)),
+            MaterialDescription::RoughDielectric { refraction, roughness } => Material::RoughDielectric(RoughDielectric::new(*refraction, *roughness)),
+            MaterialDescription::Principled(description) => {
+                let mut principled = Principled::new(self.texture(&description.base_color)?);
+                let parameters = [
+                    (&mut principled.metallic, &description.metallic),
+                    (&mut principled.roughness, &description.roughness),
+                    (&mut principled.specular, &description.specular),
+                    (&mut principled.sheen, &description.sheen),
+                    (&mut principled.clearcoat, &description.clearcoat),
+                    (&mut principled.clearcoat_roughness, &description.clearcoat_roughness),
+                    (&mut principled.transmission, &description.transmission),
+                    (&mut principled.subsurface, &description.subsurface)
+                ];
+
+                for (parameter, description) in parameters {
+                    if let Some(description) = description {
+                        *parameter = self.texture(description)?;
+                    }
+                }
+
+                if let Some(refraction) = description.refraction {
+                    principled.refraction = refraction;
+                }
+                Material::Principled(principled)
+            }
+            MaterialDescription::Emission { color } => Material::Emission(Emission::new(vec3(*color))),
+            MaterialDescription::Random => self.build_material(&random_material_description(&mut rng()))?
+        };
+        Ok(material)
+    }
+
+    fn texture(&mut self, texture: &TextureDescription) -> Result<Arc<dyn Texture>, SceneError> {
+        let texture: Arc<dyn Texture> = match texture {
+            TextureDescription::Value(value) => Arc::new(ConstantTexture::new(Vec3::new(*value, *value, *value))),
+            TextureDescription::Color(color) => Arc::new(ConstantTexture::new(vec3(*color))),
+            TextureDescription::Texture(TextureKind::Checker { even, odd, scale, uv }) => {
+                let (even, odd) = (self.texture(even)?, self.texture(odd)?);
+                if *uv {
+                    Arc::new(CheckerTexture::uv(even, odd, *scale))
+                } else {
+                    Arc::new(CheckerTexture::new(even, odd, *scale))
+                }
+            }
+            TextureDescription::Texture(TextureKind::Image { path, wrap, linear }) => {
+                let path = self.description.base_dir.join(path);
+                if let Some(texture) = self.textures.get(&(path.clone(), *linear)) {
+                    return Ok(texture.clone())
+                }
+
+                let wrap = match wrap {
+                    WrapDescription::Repeat => WrapMode::Repeat,
+                    WrapDescription::Mirror => WrapMode::Mirror,
+                    WrapDescription::Clamp => WrapMode::Clamp
+                };
+                let image = if *linear { ImageTexture::open_linear(&path, wrap) } else { ImageTexture::open(&path, wrap) };
+                let texture: Arc<dyn Texture> = Arc::new(image.map_err(|e| SceneError::Texture(path.clone(), e))?);
+                self.textures.insert((path, *linear), texture.clone());
+                texture
+            }
+            TextureDescription::Texture(TextureKind::Noise { color, scale }) => Arc::new(NoiseTexture::new(vec3(*color), *scale))
+        };
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{scene_description, test_dir, SCENE_NAMES};
+
+    const CAMERA: &str = "[camera]\nlook_from = [0.0, 0.0, 5.0]\nlook_at = [0.0, 0.0, 0.0]\nfov = 40.0\n";
+
+    fn invalid_line(source: &str) -> Option<usize> {
+        match SceneDescription::from_toml(source) {
+            Err(SceneError::Invalid { line, .. }) => line,
+            Err(e) => panic!("expected an invalid scene, got {}", e),
+            Ok(_) => panic!("expected an invalid scene")
+        }
+    }
+
+    #[test]
+    fn malformed_toml_reports_line() {
+        let source = format!("{}\n[render]\nwidth = 200\nheight = = 100\n", CAMERA);
+        match SceneDescription::from_toml(&source) {
+            Err(SceneError::Parse(e)) => assert_eq!(e.line_col().map(|(line, _)| line + 1), Some(8)),
+            _ => panic!("expected a parse error")
+        }
+    }
+
+    #[test]
+    fn zero_sizes_report_render_line() {
+        for setting in ["width = 0", "height = 0", "samples = 0", "tile_size = 0", "pass_samples = 0", "time_limit = 0.0"].iter() {
+            let source = format!("{}\n[materials.grey]\ntype = \"lambertian\"\nattenuation = 0.5\n\n[render]\n{}\n", CAMERA, setting);
+            assert_eq!(invalid_line(&source), Some(10), "{}", setting);
+        }
+    }
+
+    #[test]
+    fn invalid_objects_report_their_line() {
+        let objects = "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = { type = \"lambertian\", attenuation = 0.5 }\n\n\
+                       [[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"missing\"\n";
+        assert_eq!(invalid_line(&format!("{}\n{}", CAMERA, objects)), Some(12));
+
+        let light = "[[lights]]\ntype = \"spot\"\nposition = [0.0, 1.0, 0.0]\nlook_at = [0.0, 0.0, 0.0]\nintensity = [1.0, 1.0, 1.0]\nangle = 0.0\n";
+        assert_eq!(invalid_line(&format!("{}\n{}", CAMERA, light)), Some(6));
+    }
+
+    #[test]
+    fn built_in_scenes_round_trip() {
+        let dir = test_dir("scene-round-trip");
+
+        for name in SCENE_NAMES.iter() {
+            let description = scene_description(name).unwrap();
+            let path = dir.join(format!("{}.toml", name));
+            description.save(&path).unwrap();
+
+            let loaded = load_scene_file(&path).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(loaded.base_dir, dir);
+            assert_eq!(loaded.to_toml().unwrap(), description.to_toml().unwrap(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn overrides_match_names_by_prefix() {
+        let dir = test_dir("scene-overrides");
+        fs::write(dir.join("shapes.obj"), "\
+            o Cube.001\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n\
+            o Cube_Large\nv 0 0 1\nv 1 0 1\nv 0 1 1\nf 4 5 6\n\
+            o Sphere\nv 0 0 2\nv 1 0 2\nv 0 1 2\nf 7 8 9\n").unwrap();
+
+        let lights = |pattern: &str| {
+            let source = format!(
+                "{}\n[[objects]]\ntype = \"mesh\"\npath = \"shapes.obj\"\noverrides = {{ \"{}\" = {{ type = \"emission\", color = [1.0, 1.0, 1.0] }} }}\n",
+                CAMERA, pattern
+            );
+            fs::write(dir.join("scene.toml"), source).unwrap();
+            let (_, scene) = load_scene_file(dir.join("scene.toml")).unwrap().build(10, 10).unwrap();
+            assert_eq!(scene.world().len(), 3);
+            scene.world().objects().iter().filter(|object| object.is_light()).count()
+        };
+
+        assert_eq!(lights("Cube*"), 2);
+        assert_eq!(lights("Cube"), 0);
+        assert_eq!(lights("Cube.001"), 1);
+        assert_eq!(lights("*"), 3);
+        assert_eq!(lights("Sphere*"), 1);
+    }
+}
@@ -1,80 +1,145 @@
-use crate::objects::{HitableList, Sphere, Hitable, Triangle};
-use crate::material::Material;
-use crate::util::{load_model, Vec3, Camera};
+use crate::objects::{Bvh, Hitable, Scene};
+use crate::util::{load_model, rng, BackgroundDescription, Camera, CameraDescription, MaterialDescription, MaterialRef, ObjectDescription, RenderSettings, SceneDescription, SceneError, TextureDescription, TextureKind, Vec3};
+
+use rand::Rng;
+use std::collections::BTreeMap;
+
+// Names of the built-in scenes accepted by scene_description
+pub const SCENE_NAMES: [&str; 4] = ["simple", "random", "random2", "texture"];
+
+pub fn scene_description(name: &str) -> Option<SceneDescription> {
+    match name {
+        "simple" => Some(simple_scene_description()),
+        "random" => Some(random_scene_description()),
+        "random2" => Some(random_scene2_description()),
+        "texture" => Some(texture_scene_description()),
+        _ => None
+    }
+}
 
-use rand::{thread_rng, Rng};
+// Shows an OBJ model on its own, viewed from the front and slightly above
+pub fn model_scene(path: &str, width: usize, height: usize) -> Result<(Camera, Scene), SceneError> {
+    let mut list: Vec<Box<dyn Hitable>> = vec![];
+    load_model(&mut list, path)?;
 
-pub fn simple_scene(width: usize, height: usize) -> (Camera, HitableList) {
-    let look_from = Vec3::new(16.0, 4.0, 0.0);
-    let look_at = Vec3::new(0.0, 0.8, 0.0);
-    let dist_to_focus = (look_from - look_at).length();
-    let aperture = 0.0;
+    let world = Bvh::new(list);
+    let camera = Camera::framing(world.bounding_box(), Vec3::new(0.0, -0.3, -1.0), Vec3::new(0.0, 1.0, 0.0), 30.0, width as f64 / height as f64, 0.0);
 
-    let camera = Camera::new(look_from, look_at, Vec3::new(0.0, 1.0, 0.0), 12.0, width as f64 / height as f64, aperture, dist_to_focus);
+    Ok((camera, Scene::new(world)))
+}
 
-    let mut list: Vec<Box<dyn Hitable>> = vec![];
-    list.push(Box::new(Triangle::new(Vec3::new(1000.0, 0.0, 1000.0), Vec3::new(-1000.0, 0.0, 1000.0), Vec3::new(1000.0, 0.0, -1000.0), Vec3::new(0.0, 1.0, 0.0), Material::Lambertian {attenuation: Vec3::new(0.5, 0.5, 0.5)})));
-    list.push(Box::new(Triangle::new(Vec3::new(-1000.0, 0.0, -1000.0), Vec3::new(1000.0, 0.0, -1000.0), Vec3::new(-1000.0, 0.0, 1000.0), Vec3::new(0.0, 1.0, 0.0), Material::Lambertian {attenuation: Vec3::new(0.5, 0.5, 0.5)})));
+// Picks a mostly diffuse material, with occasional metal, glass and emitters
+pub fn random_material_description<R: Rng>(rng: &mut R) -> MaterialDescription {
+    let choose_mat = rng.gen::<f64>();
+
+    if choose_mat < 0.75 {
+        MaterialDescription::Lambertian {attenuation: TextureDescription::Color([rng.gen::<f64>() * rng.gen::<f64>(), rng.gen::<f64>() * rng.gen::<f64>(), rng.gen::<f64>() * rng.gen::<f64>()])}
+    } else if choose_mat < 0.90 {
+        MaterialDescription::Metal {attenuation: TextureDescription::Color([0.5 * (1.0 + rng.gen::<f64>()), 0.5 * (1.0 + rng.gen::<f64>()), 0.5 * (1.0 + rng.gen::<f64>())]), fuzziness: 0.5 * rng.gen::<f64>()}
+    } else if choose_mat < 0.975 {
+        MaterialDescription::Dielectric {refraction: 1.5}
+    } else {
+        MaterialDescription::Emission {color: [rng.gen::<f64>() * 10.0, rng.gen::<f64>() * 10.0, rng.gen::<f64>() * 10.0]}
+    }
+}
 
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, 1.0, -3.375), 1.0, Material::Dielectric {refraction: 1.5})));
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, 1.0, -1.125), 1.0, Material::Metal {attenuation: Vec3::new(212.0 / 255.0, 105.0 / 255.0, 33.0 / 255.0), fuzziness: 0.0})));
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, 1.0, 1.125), 1.0, Material::Lambertian {attenuation: Vec3::new(167.0 / 255.0, 51.0 / 255.0, 0.0)})));
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, 1.0, 3.375), 1.0, Material::Emission {color: Vec3::new(227.0 / 255.0 * 4.0, 160.0 / 255.0 * 4.0, 1.0)})));
+fn lambertian(color: [f64; 3]) -> MaterialRef {
+    MaterialRef::Inline(MaterialDescription::Lambertian {attenuation: TextureDescription::Color(color)})
+}
 
-    (camera, HitableList::new(list))
+fn sphere(center: [f64; 3], radius: f64, material: MaterialRef) -> ObjectDescription {
+    ObjectDescription::Sphere {center, radius, material}
 }
 
-pub fn random_scene(width: usize, height: usize) -> (Camera, HitableList) {
-    let look_from = Vec3::new(16.0, 2.0, 4.0);
-    let look_at = Vec3::new(0.0, 0.0, 0.0);
-    let dist_to_focus = (look_from - look_at).length();
-    let aperture = 0.2;
+fn camera(look_from: [f64; 3], look_at: [f64; 3], fov: f64, aperture: f64) -> CameraDescription {
+    CameraDescription {look_from, look_at, up: [0.0, 1.0, 0.0], fov, aperture, focus_distance: None}
+}
 
-    let camera = Camera::new(look_from, look_at, Vec3::new(0.0, 1.0, 0.0), 15.0, width as f64 / height as f64, aperture, dist_to_focus);
+fn scene(camera: CameraDescription, objects: Vec<ObjectDescription>) -> SceneDescription {
+    SceneDescription {
+        camera,
+        render: RenderSettings::default(),
+        background: BackgroundDescription::default(),
+        materials: BTreeMap::new(),
+        objects,
+        lights: vec![],
+        base_dir: Default::default()
+    }
+}
 
-    let mut rng = thread_rng();
-    let mut list: Vec<Box<dyn Hitable>> = vec![];
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Material::Lambertian {attenuation: Vec3::new(0.5, 0.5, 0.5)})));
+pub fn simple_scene_description() -> SceneDescription {
+    let ground = lambertian([0.5, 0.5, 0.5]);
+
+    // A dim night sky, so the emissive sphere is what lights the scene
+    let mut description = scene(camera([16.0, 4.0, 0.0], [0.0, 0.8, 0.0], 12.0, 0.0), vec![
+        ObjectDescription::Triangle {vertices: [[1000.0, 0.0, 1000.0], [-1000.0, 0.0, 1000.0], [1000.0, 0.0, -1000.0]], normal: Some([0.0, 1.0, 0.0]), material: ground.clone()},
+        ObjectDescription::Triangle {vertices: [[-1000.0, 0.0, -1000.0], [1000.0, 0.0, -1000.0], [-1000.0, 0.0, 1000.0]], normal: Some([0.0, 1.0, 0.0]), material: ground},
+
+        sphere([0.0, 1.0, -3.375], 1.0, MaterialRef::Inline(MaterialDescription::Dielectric {refraction: 1.5})),
+        sphere([0.0, 1.0, -1.125], 1.0, MaterialRef::Inline(MaterialDescription::Metal {attenuation: TextureDescription::Color([212.0 / 255.0, 105.0 / 255.0, 33.0 / 255.0]), fuzziness: 0.0})),
+        sphere([0.0, 1.0, 1.125], 1.0, lambertian([167.0 / 255.0, 51.0 / 255.0, 0.0])),
+        sphere([0.0, 1.0, 3.375], 1.0, MaterialRef::Inline(MaterialDescription::Emission {color: [227.0 / 255.0 * 4.0, 160.0 / 255.0 * 4.0, 1.0]}))
+    ]);
+    description.background = BackgroundDescription::Gradient {bottom: [0.01, 0.01, 0.015], top: [0.02, 0.03, 0.06]};
+    description
+}
+
+pub fn random_scene_description() -> SceneDescription {
+    let mut rng = rng();
+    let mut objects = vec![sphere([0.0, -1000.0, 0.0], 1000.0, lambertian([0.5, 0.5, 0.5]))];
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = rng.gen::<f64>();
             let center = Vec3::new(a as f64 + 0.9 * rng.gen::<f64>(), 0.2, b as f64 + 0.9 * rng.gen::<f64>());
             if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.75 {
-                    list.push(Box::new(Sphere::new(center, 0.2, Material::Lambertian {attenuation: Vec3::new(rng.gen::<f64>() * rng.gen::<f64>(), rng.gen::<f64>() * rng.gen::<f64>(), rng.gen::<f64>() * rng.gen::<f64>())})));
-                } else if choose_mat < 0.90 {
-                    list.push(Box::new(Sphere::new(center, 0.2, Material::Metal {attenuation: Vec3::new(0.5 * (1.0 + rng.gen::<f64>()), 0.5 * (1.0 + rng.gen::<f64>()), 0.5 * (1.0 + rng.gen::<f64>())), fuzziness: 0.5 * rng.gen::<f64>()})));
-                } else if choose_mat < 0.975 {
-                    list.push(Box::new(Sphere::new(center, 0.2, Material::Dielectric {refraction: 1.5})));
-                } else {
-                    list.push(Box::new(Sphere::new(center, 0.2, Material::Emission {color: Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()) * 10.0})));
-                }
+                objects.push(sphere([center.x, center.y, center.z], 0.2, MaterialRef::Inline(random_material_description(&mut rng))));
             }
         }
     }
 
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Material::Dielectric {refraction: 1.5})));
-    list.push(Box::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, Material::Lambertian {attenuation: Vec3::new(0.4, 0.2, 0.1)})));
-    list.push(Box::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, Material::Metal {attenuation: Vec3::new(0.7, 0.6, 0.5), fuzziness: 0.0})));
+    objects.push(sphere([0.0, 1.0, 0.0], 1.0, MaterialRef::Inline(MaterialDescription::Dielectric {refraction: 1.5})));
+    objects.push(sphere([-4.0, 1.0, 0.0], 1.0, lambertian([0.4, 0.2, 0.1])));
+    objects.push(sphere([4.0, 1.0, 0.0], 1.0, MaterialRef::Inline(MaterialDescription::Metal {attenuation: TextureDescription::Color([0.7, 0.6, 0.5]), fuzziness: 0.0})));
 
-    (camera, HitableList::new(list))
+    scene(camera([16.0, 2.0, 4.0], [0.0, 0.0, 0.0], 15.0, 0.2), objects)
 }
 
-pub fn random_scene2(width: usize, height: usize) -> (Camera, HitableList) {
-    let look_from = Vec3::new(16.0, 2.0, 4.0);
-    let look_at = Vec3::new(0.0, 0.0, 0.0);
-    let dist_to_focus = (look_from - look_at).length();
-    let aperture = 0.2;
+pub fn random_scene2_description() -> SceneDescription {
+    // The cubes in the model are given random materials to match the spheres of random_scene
+    let mut overrides = BTreeMap::new();
+    overrides.insert(String::from("Cube*"), MaterialRef::Inline(MaterialDescription::Random));
 
-    let camera = Camera::new(look_from, look_at, Vec3::new(0.0, 1.0, 0.0), 15.0, width as f64 / height as f64, aperture, dist_to_focus);
+    scene(camera([16.0, 2.0, 4.0], [0.0, 0.0, 0.0], 15.0, 0.2), vec![
+        sphere([0.0, 1.0, 0.0], 1.0, MaterialRef::Inline(MaterialDescription::Dielectric {refraction: 1.5})),
+        sphere([-4.0, 1.0, 0.0], 1.0, MaterialRef::Inline(MaterialDescription::Metal {attenuation: TextureDescription::Color([0.4, 0.2, 0.1]), fuzziness: 0.0})),
+        ObjectDescription::Mesh {path: String::from("untitled.obj"), material: None, overrides}
+    ])
+}
 
-    let mut list: Vec<Box<dyn Hitable>> = vec![];
+pub fn texture_scene_description() -> SceneDescription {
+    let white = TextureDescription::Color([0.9, 0.9, 0.9]);
+    let floor = TextureKind::Checker {even: Box::new(TextureDescription::Color([0.2, 0.3, 0.1])), odd: Box::new(white.clone()), scale: 2.0, uv: false};
+    let stripes = TextureKind::Checker {even: Box::new(TextureDescription::Color([0.8, 0.6, 0.2])), odd: Box::new(white), scale: 16.0, uv: true};
 
-    list.push(Box::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Material::Dielectric {refraction: 1.5})));
-    list.push(Box::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, Material::Metal {attenuation: Vec3::new(0.4, 0.2, 0.1), fuzziness: 0.0})));
+    scene(camera([13.0, 2.0, 3.0], [0.0, 1.0, 0.0], 20.0, 0.0), vec![
+        sphere([0.0, -1000.0, 0.0], 1000.0, MaterialRef::Inline(MaterialDescription::Lambertian {attenuation: TextureDescription::Texture(floor)})),
+        sphere([0.0, 1.0, 0.0], 1.0, MaterialRef::Inline(MaterialDescription::Lambertian {attenuation: TextureDescription::Texture(TextureKind::Noise {color: [1.0, 1.0, 1.0], scale: 4.0})})),
+        sphere([0.0, 1.0, -2.5], 1.0, MaterialRef::Inline(MaterialDescription::Metal {attenuation: TextureDescription::Texture(stripes), fuzziness: 0.1}))
+    ])
+}
 
-    load_model(&mut list, "untitled.obj");
+pub fn simple_scene(width: usize, height: usize) -> Result<(Camera, Scene), SceneError> {
+    simple_scene_description().build(width, height)
+}
 
-    (camera, HitableList::new(list))
-}
\ No newline at end of file
+pub fn random_scene(width: usize, height: usize) -> Result<(Camera, Scene), SceneError> {
+    random_scene_description().build(width, height)
+}
+
+pub fn random_scene2(width: usize, height: usize) -> Result<(Camera, Scene), SceneError> {
+    random_scene2_description().build(width, height)
+}
+
+pub fn texture_scene(width: usize, height: usize) -> Result<(Camera, Scene), SceneError> {
+    texture_scene_description().build(width, height)
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+// Order in which the tiles of an image are handed out to the render threads
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TileOrder {
+    // Row by row from the top left
+    Scanline,
+    // Outwards from the center of the image, where the subject usually is
+    #[default]
+    Spiral,
+    // Along a Hilbert curve, so tiles being worked on at the same time stay close together
+    Hilbert
+}
+
+pub const TILE_ORDER_NAMES: [&str; 3] = ["scanline", "spiral", "hilbert"];
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        match name.to_lowercase().as_str() {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "hilbert" => Some(TileOrder::Hilbert),
+            _ => None
+        }
+    }
+}
+
+// Block of pixels from x0 to x1 and y0 to y1, exclusive of the end. The index is the position of the
+// tile in the grid counted row by row, whatever order it is rendered in.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Tile {
+    pub index: usize,
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize
+}
+
+impl Tile {
+    pub fn pixel_count(&self) -> usize {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+}
+
+// Splits an image into square tiles of the given size, smaller along the right and bottom edges
+// when it does not divide the image, listed in the order they should be rendered
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let tile = |column: usize, row: usize| Tile {
+        index: row * columns + column,
+        x0: column * size,
+        y0: row * size,
+        x1: ((column + 1) * size).min(width),
+        y1: ((row + 1) * size).min(height)
+    };
+
+    let cells = match order {
+        TileOrder::Scanline => (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => hilbert(columns, rows)
+    };
+    cells.into_iter().map(|(column, row)| tile(column, row)).collect()
+}
+
+// Walks right, down, left and up around the center cell with runs growing by one every second turn,
+// keeping the cells that lie inside the grid
+fn spiral(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    const STEPS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+    let count = columns * rows;
+    let mut cells = Vec::with_capacity(count);
+    let (mut x, mut y) = (((columns as isize) - 1) / 2, ((rows as isize) - 1) / 2);
+    let (mut direction, mut run) = (0, 1);
+
+    while cells.len() < count {
+        for _ in 0..2 {
+            for _ in 0..run {
+                if x >= 0 && y >= 0 && (x as usize) < columns && (y as usize) < rows {
+                    cells.push((x as usize, y as usize));
+                }
+                x += STEPS[direction].0;
+                y += STEPS[direction].1;
+            }
+            direction = (direction + 1) % 4;
+        }
+        run += 1;
+    }
+    cells
+}
+
+// Follows the curve over the smallest power of two square covering the grid, skipping what lies outside
+fn hilbert(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let n = columns.max(rows).next_power_of_two();
+
+    (0..n * n)
+        .map(|d| hilbert_point(n, d))
+        .filter(|&(x, y)| x < columns && y < rows)
+        .collect()
+}
+
+// Position of the distance d along the Hilbert curve filling an n by n square
+fn hilbert_point(n: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y, mut t) = (0, 0, d);
+    let mut s = 1;
+
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+
+        // Each quadrant is the curve turned so its ends meet those of its neighbours
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+    // Every tile of the grid comes once, and together they cover every pixel once
+    fn assert_covers(width: usize, height: usize, size: usize, order: TileOrder) {
+        let list = tiles(width, height, size, order);
+        let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+        assert_eq!(list.len(), columns * rows, "{}x{} in tiles of {} by {:?}", width, height, size, order);
+
+        let mut seen = vec![false; list.len()];
+        let mut pixels = vec![0; width * height];
+        for tile in list.iter() {
+            assert!(!seen[tile.index], "tile {} twice in {}x{} by {:?}", tile.index, width, height, order);
+            seen[tile.index] = true;
+            assert_eq!((tile.x0 / size, tile.y0 / size), (tile.index % columns, tile.index / columns));
+            assert!(tile.x0 < tile.x1 && tile.x1 <= width && tile.y0 < tile.y1 && tile.y1 <= height);
+
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    pixels[y * width + x] += 1;
+                }
+            }
+        }
+        assert!(pixels.iter().all(|&count| count == 1), "{}x{} in tiles of {} by {:?}", width, height, size, order);
+        assert_eq!(list.iter().map(Tile::pixel_count).sum::<usize>(), width * height);
+    }
+
+    #[test]
+    fn every_order_covers_every_tile_once() {
+        let grids = [(5, 3), (1, 7), (7, 1), (1, 1), (3, 5), (6, 6), (9, 2), (17, 11)];
+        for &(columns, rows) in grids.iter() {
+            for &order in ORDERS.iter() {
+                assert_covers(columns, rows, 1, order);
+                // Edge tiles smaller than the rest
+                assert_covers(columns * 4 - 1, rows * 4 - 3, 4, order);
+            }
+        }
+    }
+
+    #[test]
+    fn spiral_starts_in_the_middle() {
+        let list = tiles(50, 30, 10, TileOrder::Spiral);
+        assert_eq!((list[0].x0, list[0].y0), (20, 10));
+    }
+
+    #[test]
+    fn hilbert_moves_to_a_neighbour() {
+        // On a power of two grid each step of the curve goes to an adjacent tile
+        let list = tiles(8, 8, 1, TileOrder::Hilbert);
+        for pair in list.windows(2) {
+            let distance = (pair[0].x0 as isize - pair[1].x0 as isize).abs() + (pair[0].y0 as isize - pair[1].y0 as isize).abs();
+            assert_eq!(distance, 1);
+        }
+    }
+
+    #[test]
+    fn names() {
+        for (&name, &order) in TILE_ORDER_NAMES.iter().zip(ORDERS.iter()) {
+            assert_eq!(TileOrder::from_name(name), Some(order));
+        }
+        assert_eq!(TileOrder::from_name("Hilbert"), Some(TileOrder::Hilbert));
+        assert_eq!(TileOrder::from_name("random"), None);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::util::Vec3;
+use serde::{Deserialize, Serialize};
+
+// Curves bringing scene radiance into the displayable range. Every operator returns linear values
+// between 0 and 1, which are then encoded for display with the sRGB transfer function.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapOperator {
+    // Clips anything brighter than white
+    #[default]
+    Clamp,
+    // x / (1 + x) on each channel, which never clips but flattens highlights
+    Reinhard,
+    // Stephen Hill's fit of the ACES reference rendering and sRGB output transforms
+    Aces,
+    // Troy Sobotka's AgX as fitted by Benjamin Wrensch, desaturating bright colors towards white
+    Agx
+}
+
+const MAX_RADIANCE: f64 = 1e12;
+
+pub const TONE_MAP_NAMES: [&str; 4] = ["clamp", "reinhard", "aces", "agx"];
+
+impl ToneMapOperator {
+    pub fn from_name(name: &str) -> Option<ToneMapOperator> {
+        match name.to_lowercase().as_str() {
+            "clamp" => Some(ToneMapOperator::Clamp),
+            "reinhard" => Some(ToneMapOperator::Reinhard),
+            "aces" => Some(ToneMapOperator::Aces),
+            "agx" => Some(ToneMapOperator::Agx),
+            _ => None
+        }
+    }
+
+    pub fn apply(self, color: Vec3) -> Vec3 {
+        // from dividing infinities. Negative and NaN radiance is black.
+        // from dividing infinities
+        let limit = |c: f64| if c > 0.0 { c.min(MAX_RADIANCE) } else { 0.0 };
+        let color = Vec3::new(limit(color.x), limit(color.y), limit(color.z));
+
+        let mapped = match self {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => Vec3::new(color.x / (1.0 + color.x), color.y / (1.0 + color.y), color.z / (1.0 + color.z)),
+            ToneMapOperator::Aces => aces(color),
+            ToneMapOperator::Agx => agx(color)
+        };
+        Vec3::new(mapped.x.clamp(0.0, 1.0), mapped.y.clamp(0.0, 1.0), mapped.z.clamp(0.0, 1.0))
+    }
+}
+
+// Exposure adjustment and tone mapping applied when radiance is turned into an 8-bit image
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct ToneMap {
+    pub operator: ToneMapOperator,
+    // Stops of exposure, each doubling or halving the radiance before it is mapped
+    pub exposure: f64
+}
+
+impl ToneMap {
+    pub fn new(operator: ToneMapOperator, exposure: f64) -> ToneMap {
+        ToneMap {
+            operator,
+            exposure
+        }
+    }
+
+    // Display-linear color between 0 and 1
+    pub fn map(&self, color: Vec3) -> Vec3 {
+        self.operator.apply(color * 2f64.powf(self.exposure))
+    }
+
+    pub fn to_srgb8(&self, color: Vec3) -> [u8; 3] {
+        let mapped = self.map(color);
+        let encode = |c: f64| (linear_to_srgb(c) * 255.0).round() as u8;
+        [encode(mapped.x), encode(mapped.y), encode(mapped.z)]
+    }
+}
+
+// The sRGB opto-electronic transfer function, with its linear segment near black
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn multiply(m: [[f64; 3]; 3], v: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z
+    )
+}
+
+fn aces(color: Vec3) -> Vec3 {
+    // Linear sRGB to the ACES rendering space, and back again after the curve
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777]
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602]
+    ];
+
+    let curve = |v: f64| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081);
+    let v = multiply(INPUT, color);
+    multiply(OUTPUT, Vec3::new(curve(v.x), curve(v.y), curve(v.z)))
+}
+
+fn agx(color: Vec3) -> Vec3 {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104]
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116]
+    ];
+    // Range of exposures around middle grey kept by the log encoding
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let curve = |v: f64| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    };
+
+    let v = multiply(INSET, color);
+    let v = multiply(OUTSET, Vec3::new(curve(v.x), curve(v.y), curve(v.z)));
+
+    // The curve produces display-encoded values, which are taken back to linear with a 2.2 gamma
+    Vec3::new(v.x.max(0.0).powf(2.2), v.y.max(0.0).powf(2.2), v.z.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapOperator; 4] = [ToneMapOperator::Clamp, ToneMapOperator::Reinhard, ToneMapOperator::Aces, ToneMapOperator::Agx];
+
+    // Radiance from far below to far above white, in steps of a sixteenth of a stop
+    fn exposures() -> impl Iterator<Item = f64> {
+        (-320..=640).map(|step| 2f64.powf(step as f64 / 16.0))
+    }
+
+    #[test]
+    fn srgb_segments_meet() {
+        let threshold: f64 = 0.0031308;
+        let (below, above) = (linear_to_srgb(threshold), linear_to_srgb(threshold.next_up()));
+        assert!((below - above).abs() < 1e-6, "{} against {}", below, above);
+
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-12);
+        assert!((linear_to_srgb(0.18) - 0.4613561).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clamp_to_srgb8() {
+        let tone_map = ToneMap::new(ToneMapOperator::Clamp, 0.0);
+        assert_eq!(tone_map.to_srgb8(Vec3::new(0.0, 0.0, 0.0)), [0, 0, 0]);
+        assert_eq!(tone_map.to_srgb8(Vec3::new(1.0, 1.0, 1.0)), [255, 255, 255]);
+        assert_eq!(tone_map.to_srgb8(Vec3::new(-1.0, 4.0, f64::NAN)), [0, 255, 0]);
+        assert_eq!(tone_map.to_srgb8(Vec3::new(0.18, 0.5, 0.0031308)), [118, 188, 10]);
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        let colors = [Vec3::new(1.0, 1.0, 1.0), Vec3::new(1.0, 0.5, 0.1), Vec3::new(0.05, 0.2, 1.0)];
+        for &operator in OPERATORS.iter() {
+            for &color in colors.iter() {
+                let mut previous = Vec3::new(0.0, 0.0, 0.0);
+                for scale in exposures().chain([1e30, f64::MAX, f64::INFINITY].iter().cloned()) {
+                    let mapped = operator.apply(color * scale);
+                    for c in 0..3 {
+                        assert!((0.0..=1.0).contains(&mapped[c]), "{:?} of {} at {}: {}", operator, c, scale, mapped[c]);
+                    }
+
+                    // Each channel follows the radiance of a grey, and the brightness that of a color
+                    let brightness = |v: Vec3| v.x + v.y + v.z;
+                    if color.x == color.y {
+                        for c in 0..3 {
+                            assert!(mapped[c] >= previous[c], "{:?} of {} at {}: {} after {}", operator, c, scale, mapped[c], previous[c]);
+                        }
+                    }
+                    assert!(brightness(mapped) >= brightness(previous) - 1e-12, "{:?} at {}", operator, scale);
+                    previous = mapped;
+                }
+                assert!(previous.x > 0.95 && previous.y > 0.5, "{:?} leaves {:?} dark", operator, [previous.x, previous.y, previous.z]);
+            }
+        }
+    }
+
+    #[test]
+    fn exposure_scales_by_stops() {
+        for &operator in OPERATORS.iter() {
+            for &stops in [-3.0, -0.5, 0.0, 1.0, 2.5].iter() {
+                for scale in exposures().step_by(37) {
+                    let color = Vec3::new(0.7, 0.4, 0.1) * scale;
+                    let (exposed, scaled) = (ToneMap::new(operator, stops).map(color), operator.apply(color * 2f64.powf(stops)));
+                    assert_eq!([exposed.x, exposed.y, exposed.z], [scaled.x, scaled.y, scaled.z]);
+                }
+            }
+        }
+        let tone_map = ToneMap::new(ToneMapOperator::Clamp, -2.0);
+        let mapped = tone_map.map(Vec3::new(2.0, 0.4, 8.0));
+        assert_eq!([mapped.x, mapped.y, mapped.z], [0.5, 0.1, 1.0]);
+    }
+}
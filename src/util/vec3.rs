@@ -1,6 +1,5 @@
 use std::ops;
 use rand::Rng;
-use rand::prelude::ThreadRng;
 
 #[derive(Copy, Clone)]
 pub struct Vec3 {
@@ -42,7 +41,17 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_sphere(mut rng: ThreadRng) -> Vec3 {
+    // Component-wise minimum of two vectors
+    pub fn min(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.x.min(v.x), self.y.min(v.y), self.z.min(v.z))
+    }
+
+    // Component-wise maximum of two vectors
+    pub fn max(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.x.max(v.x), self.y.max(v.y), self.z.max(v.z))
+    }
+
+    pub fn random_in_unit_sphere<R: Rng>(rng: &mut R) -> Vec3 {
         let mut p = Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()) * 2.0 - Vec3::new(1.0, 1.0, 1.0);
 
         while p.squared_length() >= 1.0 {
@@ -52,6 +61,18 @@ impl Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis index out of range: {}", axis)
+        }
+    }
+}
+
 impl ops::Add<Vec3> for Vec3 {
     type Output = Vec3;
     fn add(self, v: Vec3) -> Vec3 {